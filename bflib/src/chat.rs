@@ -0,0 +1,90 @@
+use crate::{db::SpawnLoc, Context};
+use dcso3::{coalition::Side, env::miz::GroupKind, String, Vector2};
+use fxhash::FxHashMap;
+use mlua::prelude::*;
+
+/// messages starting with this character are treated as admin commands and
+/// swallowed rather than broadcast to the rest of the chat
+pub const PREFIX: char = '-';
+
+pub type Handler = fn(&mut Context, &Lua, u32, &[&str]);
+
+/// a simple, extensible registry of admin chat commands. other modules can
+/// call `register` to add their own verbs without touching this file.
+#[derive(Default)]
+pub struct Commands {
+    handlers: FxHashMap<String, Handler>,
+}
+
+impl Commands {
+    pub fn register(&mut self, name: &str, f: Handler) {
+        self.handlers.insert(String::from(name), f);
+    }
+
+    /// tokenize and dispatch `msg`. returns `true` if `msg` was a
+    /// recognized command, in which case the caller should swallow it
+    /// instead of broadcasting it to the rest of the chat
+    pub fn dispatch(&self, ctx: &mut Context, lua: &Lua, id: u32, msg: &str) -> bool {
+        let rest = match msg.strip_prefix(PREFIX) {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let mut parts = rest.split_whitespace();
+        let cmd = match parts.next() {
+            Some(c) => c,
+            None => return false,
+        };
+        match self.handlers.get(cmd) {
+            None => false,
+            Some(f) => {
+                let args: Vec<&str> = parts.collect();
+                f(ctx, lua, id, &args);
+                true
+            }
+        }
+    }
+}
+
+pub fn register_builtins(cmds: &mut Commands) {
+    cmds.register("spawn", cmd_spawn);
+    cmds.register("groups", cmd_groups);
+    cmds.register("respawn", cmd_respawn);
+}
+
+/// `-spawn <blue|red> <trigger_zone> <template>`
+fn cmd_spawn(ctx: &mut Context, lua: &Lua, _id: u32, args: &[&str]) {
+    if args.len() < 3 {
+        println!("usage: -spawn <blue|red> <trigger_zone> <template>");
+        return;
+    }
+    let side = match args[0] {
+        "blue" => Side::Blue,
+        "red" => Side::Red,
+        s => {
+            println!("unknown side {}", s);
+            return;
+        }
+    };
+    let loc = SpawnLoc::AtTrigger {
+        name: String::from(args[1]),
+        offset: Vector2::new(0., 0.),
+    };
+    match ctx.spawn_template_as_new(lua, side, GroupKind::Vehicle, &loc, args[2]) {
+        Ok(gid) => println!("spawned {} as {gid}", args[2]),
+        Err(e) => println!("spawn failed: {:?}", e),
+    }
+}
+
+/// `-groups` lists every live group tracked by the `Db`
+fn cmd_groups(ctx: &mut Context, _lua: &Lua, _id: u32, _args: &[&str]) {
+    for (gid, group) in ctx.db.groups() {
+        println!("{gid}: {} ({:?})", group.name, group.side);
+    }
+}
+
+/// `-respawn` forces every tracked group to respawn
+fn cmd_respawn(ctx: &mut Context, lua: &Lua, _id: u32, _args: &[&str]) {
+    if let Err(e) = ctx.respawn_groups(lua) {
+        println!("respawn failed: {:?}", e)
+    }
+}
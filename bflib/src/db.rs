@@ -7,14 +7,20 @@ use dcso3::{
     group::GroupCategory,
     DeepClone, String, Vector2,
 };
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use immutable_chunkmap::{map::MapM as Map, set::SetM as Set};
 use mlua::prelude::*;
 use serde_derive::{Deserialize, Serialize};
 use std::{
+    cell::RefCell,
     fmt::Display,
     fs::{self, File},
-    path::{Path, PathBuf}, sync::atomic::{AtomicU64, Ordering},
+    hash::Hasher,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+    },
 };
 
 #[derive(
@@ -174,15 +180,53 @@ impl<'lua> SpawnCtx<'lua> {
     }
 }
 
+/// how many events a subscriber's channel will buffer before `publish`
+/// starts dropping them; once a subscriber's buffer is full, further events
+/// for it are dropped (its existing backlog is kept) until it drains, rather
+/// than ever blocking the sim tick
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// side length in meters of a spatial hash grid cell, chosen on the order
+/// of the largest expected proximity query radius (SAM engagement ranges,
+/// capture zones) so a query only has to scan a handful of cells
+const CELL: f64 = 2000.;
+
+fn cell_of(pos: Vector2) -> (i32, i32) {
+    // floored, not truncated, division so cells are contiguous across 0
+    ((pos.x / CELL).floor() as i32, (pos.y / CELL).floor() as i32)
+}
+
+fn cells_in_radius(center: Vector2, radius: f64) -> impl Iterator<Item = (i32, i32)> {
+    let (x0, y0) = cell_of(Vector2::new(center.x - radius, center.y - radius));
+    let (x1, y1) = cell_of(Vector2::new(center.x + radius, center.y + radius));
+    (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+}
+
+/// a registered [`Db::subscribe`] listener, narrowed to the `DbEvent::kind`
+/// and/or `Side` it cares about so it only wakes for relevant events
+#[derive(Debug, Clone)]
+struct Subscriber {
+    tx: SyncSender<DbEvent>,
+    kind: Option<EventKind>,
+    side: Option<Side>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Db {
     #[serde(skip)]
     dirty: bool,
+    #[serde(skip)]
+    subscribers: RefCell<Vec<Subscriber>>,
     groups_by_id: Map<GroupId, SpawnedGroup>,
     units_by_id: Map<UnitId, SpawnedUnit>,
     groups_by_name: Map<String, GroupId>,
     units_by_name: Map<String, UnitId>,
     groups_by_side: Map<Side, Set<GroupId>>,
+    /// spatial hash grid over live units' positions, keyed by
+    /// `(floor(pos.x / CELL), floor(pos.y / CELL))`, so `units_in_radius`
+    /// and `groups_in_radius` only have to scan the handful of cells a
+    /// query circle's bounding box overlaps instead of every unit in the db
+    grid: Map<(i32, i32), Set<UnitId>>,
 }
 
 impl Db {
@@ -226,7 +270,39 @@ impl Db {
         }
     }
 
+    /// subscribe to a filtered stream of [`DbEvent`]s, published
+    /// synchronously whenever `spawn_template_as_new`, `unit_dead`, or
+    /// `respawn_group` mutate state. `kind`/`side` narrow the subscription
+    /// to just the events a consumer cares about; pass `None` to match
+    /// anything. publishing never blocks the sim tick: a subscriber that
+    /// falls behind just misses events once its buffer of
+    /// `SUBSCRIBER_BUFFER` fills up, rather than stalling the publisher
+    pub fn subscribe(&self, kind: Option<EventKind>, side: Option<Side>) -> Receiver<DbEvent> {
+        let (tx, rx) = sync_channel(SUBSCRIBER_BUFFER);
+        self.subscribers.borrow_mut().push(Subscriber { tx, kind, side });
+        rx
+    }
+
+    fn publish(&self, ev: &DbEvent, side: Side) {
+        self.subscribers.borrow_mut().retain(|sub| {
+            if sub.kind.map(|k| k == ev.kind()).unwrap_or(true)
+                && sub.side.map(|s| s == side).unwrap_or(true)
+            {
+                match sub.tx.try_send(ev.clone()) {
+                    Ok(()) | Err(TrySendError::Full(_)) => true,
+                    Err(TrySendError::Disconnected(_)) => false,
+                }
+            } else {
+                true
+            }
+        });
+    }
+
     pub fn unit_dead(&mut self, id: UnitId, dead: bool) {
+        let info = self.units_by_id.get(&id).map(|u| (u.group, u.pos));
+        let side = info
+            .and_then(|(gid, _)| self.groups_by_id.get(&gid))
+            .map(|g| g.side);
         self.units_by_id.update_cow(id, (), |id, (), unit| {
             unit.map(|(_, unit)| {
                 let unit = SpawnedUnit {
@@ -236,9 +312,86 @@ impl Db {
                 (id, unit)
             })
         });
+        if let Some((_, pos)) = info {
+            let cell = cell_of(pos);
+            if dead {
+                if let Some(set) = self.grid.get_mut_cow(&cell) {
+                    set.remove_cow(&id);
+                }
+            } else {
+                self.grid.get_or_default_cow(cell).insert_cow(id);
+            }
+        }
+        self.dirty = true;
+        if let Some(side) = side {
+            self.publish(&DbEvent::UnitDead { id }, side);
+        }
+    }
+
+    /// move a live unit to `pos`, keeping its spatial hash grid cell in
+    /// sync so `units_in_radius`/`groups_in_radius` stay accurate
+    pub fn move_unit(&mut self, id: UnitId, pos: Vector2) {
+        let old_pos = self.units_by_id.get(&id).map(|u| u.pos);
+        self.units_by_id.update_cow(id, (), |id, (), unit| {
+            unit.map(|(_, unit)| (id, SpawnedUnit { pos, ..unit.clone() }))
+        });
+        if let Some(old_pos) = old_pos {
+            let old_cell = cell_of(old_pos);
+            let new_cell = cell_of(pos);
+            if old_cell != new_cell {
+                if let Some(set) = self.grid.get_mut_cow(&old_cell) {
+                    set.remove_cow(&id);
+                }
+                self.grid.get_or_default_cow(new_cell).insert_cow(id);
+            }
+        }
         self.dirty = true;
     }
 
+    pub fn remove_group(&mut self, id: GroupId) {
+        if let Some(group) = self.groups_by_id.remove_cow(&id) {
+            self.groups_by_name.remove_cow(&group.name);
+            for uid in &group.units {
+                if let Some(unit) = self.units_by_id.remove_cow(uid) {
+                    self.units_by_name.remove_cow(&unit.name);
+                    if let Some(set) = self.grid.get_mut_cow(&cell_of(unit.pos)) {
+                        set.remove_cow(uid);
+                    }
+                }
+            }
+            self.dirty = true;
+        }
+    }
+
+    /// every live unit within `radius` meters of `center`, found by
+    /// scanning only the spatial hash grid cells the query circle's
+    /// bounding box overlaps and filtering by true Euclidean distance
+    pub fn units_in_radius(&self, center: Vector2, radius: f64) -> impl Iterator<Item = &SpawnedUnit> {
+        let r2 = radius * radius;
+        cells_in_radius(center, radius)
+            .filter_map(move |cell| self.grid.get(&cell))
+            .flat_map(|set| set.into_iter())
+            .filter_map(move |uid| self.units_by_id.get(uid))
+            .filter(move |u| {
+                let d = u.pos - center;
+                !u.dead && d.x * d.x + d.y * d.y <= r2
+            })
+    }
+
+    /// every group with at least one live unit within `radius` meters of
+    /// `center`, deduplicated
+    pub fn groups_in_radius(&self, center: Vector2, radius: f64) -> impl Iterator<Item = &SpawnedGroup> {
+        let mut seen: FxHashSet<GroupId> = FxHashSet::default();
+        self.units_in_radius(center, radius)
+            .filter_map(move |u| {
+                if seen.insert(u.group) {
+                    self.groups_by_id.get(&u.group)
+                } else {
+                    None
+                }
+            })
+    }
+
     pub fn groups(&self) -> impl Iterator<Item = (&GroupId, &SpawnedGroup)> {
         self.groups_by_id.into_iter()
     }
@@ -259,12 +412,15 @@ impl Db {
         self.units_by_name.get(name).and_then(|uid| self.get_unit(uid))
     }
 
+    /// respawn `group` in game from the units still marked alive in the
+    /// `Db`. returns `true` if anything was actually spawned, so callers
+    /// can decide whether a [`DbEvent::GroupSpawned`] is worth journaling
     pub fn respawn_group<'lua>(
         &self,
         idx: &MizIndex,
         spctx: &SpawnCtx,
         group: &SpawnedGroup,
-    ) -> LuaResult<()> {
+    ) -> LuaResult<bool> {
         let template =
             spctx.get_template(idx, group.kind, group.side, group.template_name.as_str())?;
         template.group.set("lateActivation", false)?;
@@ -299,10 +455,10 @@ impl Db {
             units.len() > 0
         };
         if alive {
-            spctx.spawn(template)
-        } else {
-            Ok(())
+            spctx.spawn(template)?;
+            self.publish(&DbEvent::GroupSpawned(group.clone()), group.side);
         }
+        Ok(alive)
     }
 
     pub fn spawn_template_as_new<'lua>(
@@ -357,13 +513,335 @@ impl Db {
                 dead: false,
             };
             spawned.units.insert_cow(uid);
-            self.units_by_id.insert_cow(uid, spawned_unit);
+            self.units_by_id.insert_cow(uid, spawned_unit.clone());
             self.units_by_name.insert_cow(unit_name, uid);
+            self.grid.get_or_default_cow(cell_of(pos)).insert_cow(uid);
+            self.publish(&DbEvent::UnitSpawned(spawned_unit), side);
         }
-        self.groups_by_id.insert_cow(gid, spawned);
+        self.groups_by_id.insert_cow(gid, spawned.clone());
         self.groups_by_name.insert_cow(group_name, gid);
         self.dirty = true;
         spctx.spawn(template)?;
+        self.publish(&DbEvent::GroupSpawned(spawned), side);
         Ok(gid)
     }
+
+    /// instantiate an entire TOML spawn plan (see the `plan` module) in one
+    /// call, returning the id of every group it spawned. a mission author
+    /// can describe a whole ORBAT in a config file instead of one
+    /// `spawn_template_as_new` call per group
+    pub fn apply_plan<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        idx: &MizIndex,
+        plan: &Path,
+    ) -> LuaResult<Vec<GroupId>> {
+        let entries = crate::plan::load(plan).map_err(|e| {
+            println!("failed to load spawn plan {:?}, {:?}", plan, e);
+            err("invalid spawn plan")
+        })?;
+        entries
+            .into_iter()
+            .map(|e| {
+                self.spawn_template_as_new(
+                    lua,
+                    idx,
+                    e.side,
+                    e.kind,
+                    &e.location,
+                    e.template_name.as_str(),
+                )
+            })
+            .collect()
+    }
+}
+
+const KEEP_MANIFESTS: usize = 8;
+
+fn hash_blob(bytes: &[u8]) -> std::string::String {
+    let mut h = fxhash::FxHasher::default();
+    h.write(bytes);
+    format!("{:016x}", h.finish())
+}
+
+fn blob_path(dir: &Path, hash: &str) -> PathBuf {
+    let mut p = dir.join("blobs").join(hash);
+    p.set_extension("json");
+    p
+}
+
+/// the small, cheap to write root of a snapshot; everything bulky lives in
+/// the content addressed blob store and is only referenced by hash
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RootManifest {
+    tick: u64,
+    groups: FxHashMap<GroupId, std::string::String>,
+}
+
+/// a single append only record of something that happened to the `Db`.
+/// appended to the journal by the callers of the mutating methods instead of requiring a
+/// full snapshot on every change, so per-tick I/O is proportional to the
+/// number of changes rather than the size of the world; replayed by
+/// [`Db::replay`]/[`Db::load_content_addressed`] to reconstruct state
+/// between periodic compacting snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DbEvent {
+    GroupSpawned(SpawnedGroup),
+    UnitSpawned(SpawnedUnit),
+    UnitMoved { id: UnitId, pos: Vector2 },
+    UnitDead { id: UnitId },
+    GroupRemoved(GroupId),
+}
+
+/// the tag of a [`DbEvent`], used by [`Db::subscribe`] to filter a
+/// subscription down to just the kinds of event a consumer cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    GroupSpawned,
+    UnitSpawned,
+    UnitMoved,
+    UnitDead,
+    GroupRemoved,
+}
+
+impl DbEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            DbEvent::GroupSpawned(_) => EventKind::GroupSpawned,
+            DbEvent::UnitSpawned(_) => EventKind::UnitSpawned,
+            DbEvent::UnitMoved { .. } => EventKind::UnitMoved,
+            DbEvent::UnitDead { .. } => EventKind::UnitDead,
+            DbEvent::GroupRemoved(_) => EventKind::GroupRemoved,
+        }
+    }
+}
+
+fn journal_path(dir: &Path) -> PathBuf {
+    dir.join("journal.ndjson")
+}
+
+fn manifest_path(dir: &Path, tick: u64) -> PathBuf {
+    dir.join(format!("manifest-{:020}.json", tick))
+}
+
+impl Db {
+    /// write a content addressed snapshot of `self` into `dir`. groups whose
+    /// serialized bytes are unchanged since the last snapshot simply share
+    /// the existing blob instead of being rewritten, turning a full
+    /// `O(all groups)` write into `O(changed groups)`. keeps the last
+    /// `KEEP_MANIFESTS` root manifests on disk so the server can roll back
+    /// to an earlier tick, and truncates the journal since everything in it
+    /// is now reflected in the new manifest.
+    pub fn save_content_addressed(&self, dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(dir.join("blobs"))?;
+        let tick = self
+            .latest_manifest(dir)?
+            .map(|m| m.tick + 1)
+            .unwrap_or(0);
+        let mut manifest = RootManifest {
+            tick,
+            groups: FxHashMap::default(),
+        };
+        for (gid, group) in &self.groups_by_id {
+            let units: Vec<&SpawnedUnit> = group
+                .units
+                .into_iter()
+                .filter_map(|uid| self.units_by_id.get(uid))
+                .collect();
+            let bytes = serde_json::to_vec(&(group, units))?;
+            let hash = hash_blob(&bytes);
+            let path = blob_path(dir, &hash);
+            if !path.exists() {
+                fs::write(path, &bytes)?;
+            }
+            manifest.groups.insert(*gid, hash);
+        }
+        let path = manifest_path(dir, tick);
+        let mut tmp = path.clone();
+        tmp.set_extension("tmp");
+        serde_json::to_writer(File::create(&tmp)?, &manifest)?;
+        fs::rename(tmp, path)?;
+        self.prune_manifests(dir, KEEP_MANIFESTS)?;
+        if journal_path(dir).exists() {
+            fs::remove_file(journal_path(dir))?;
+        }
+        Ok(())
+    }
+
+    fn latest_manifest(&self, dir: &Path) -> anyhow::Result<Option<RootManifest>> {
+        let mut best: Option<(u64, PathBuf)> = None;
+        if dir.exists() {
+            for ent in fs::read_dir(dir)? {
+                let ent = ent?;
+                let name = ent.file_name();
+                let name = name.to_string_lossy();
+                if let Some(rest) = name
+                    .strip_prefix("manifest-")
+                    .and_then(|s| s.strip_suffix(".json"))
+                {
+                    if let Ok(tick) = rest.parse::<u64>() {
+                        if best.as_ref().map(|(t, _)| tick > *t).unwrap_or(true) {
+                            best = Some((tick, ent.path()));
+                        }
+                    }
+                }
+            }
+        }
+        match best {
+            None => Ok(None),
+            Some((_, path)) => Ok(Some(serde_json::from_reader(File::open(path)?)?)),
+        }
+    }
+
+    fn prune_manifests(&self, dir: &Path, keep: usize) -> anyhow::Result<()> {
+        let mut manifests: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name();
+                let name = name.to_string_lossy();
+                let rest = name.strip_prefix("manifest-")?.strip_suffix(".json")?;
+                Some((rest.parse::<u64>().ok()?, e.path()))
+            })
+            .collect();
+        manifests.sort_by_key(|(tick, _)| *tick);
+        if manifests.len() > keep {
+            for (_, path) in &manifests[..manifests.len() - keep] {
+                let _ = fs::remove_file(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// reconstruct a `Db` from the newest root manifest in `dir`, resolving
+    /// each group's blob, then replaying any journal entries appended after
+    /// that manifest was written
+    pub fn load_content_addressed(dir: &Path) -> LuaResult<Self> {
+        let manifest = Self::load_latest_manifest(dir)
+            .map_err(|e| {
+                println!("failed to read root manifest in {:?}, {:?}", dir, e);
+                err("decode error")
+            })?
+            .ok_or_else(|| err("no snapshot found"))?;
+        let mut db = Db::default();
+        for (gid, hash) in &manifest.groups {
+            let path = blob_path(dir, hash);
+            let file = File::open(&path).map_err(|e| {
+                println!("failed to open blob {:?}, {:?}", path, e);
+                err("io error")
+            })?;
+            let (group, units): (SpawnedGroup, Vec<SpawnedUnit>) =
+                serde_json::from_reader(file).map_err(|e| {
+                    println!("failed to decode blob {:?}, {:?}", path, e);
+                    err("decode error")
+                })?;
+            GroupId::update_max(*gid);
+            db.groups_by_name.insert_cow(group.name.clone(), *gid);
+            db.groups_by_id.insert_cow(*gid, group);
+            for unit in units {
+                UnitId::update_max(unit.id);
+                db.units_by_name.insert_cow(unit.name.clone(), unit.id);
+                db.grid.get_or_default_cow(cell_of(unit.pos)).insert_cow(unit.id);
+                db.units_by_id.insert_cow(unit.id, unit);
+            }
+        }
+        db.replay_journal(dir).map_err(|e| {
+            println!("failed to replay journal in {:?}, {:?}", dir, e);
+            err("decode error")
+        })?;
+        Ok(db)
+    }
+
+    fn load_latest_manifest(dir: &Path) -> anyhow::Result<Option<RootManifest>> {
+        Db::default().latest_manifest(dir)
+    }
+
+    /// fold a single `DbEvent` into `self`, re-running `update_max` for
+    /// every id it carries so the atomic counters stay monotone after a
+    /// replay. events must be applied in file order: `UnitMoved`/`UnitDead`
+    /// assume the `UnitSpawned` they refer to was already applied
+    fn apply_event(&mut self, ev: DbEvent) {
+        match ev {
+            DbEvent::GroupSpawned(group) => {
+                GroupId::update_max(group.id);
+                for uid in &group.units {
+                    UnitId::update_max(*uid);
+                }
+                self.groups_by_name.insert_cow(group.name.clone(), group.id);
+                self.groups_by_id.insert_cow(group.id, group);
+            }
+            DbEvent::UnitSpawned(unit) => {
+                UnitId::update_max(unit.id);
+                self.units_by_name.insert_cow(unit.name.clone(), unit.id);
+                self.grid.get_or_default_cow(cell_of(unit.pos)).insert_cow(unit.id);
+                self.units_by_id.insert_cow(unit.id, unit);
+            }
+            DbEvent::UnitMoved { id, pos } => {
+                UnitId::update_max(id);
+                self.move_unit(id, pos);
+            }
+            DbEvent::UnitDead { id } => {
+                UnitId::update_max(id);
+                self.unit_dead(id, true);
+            }
+            DbEvent::GroupRemoved(id) => {
+                GroupId::update_max(id);
+                self.remove_group(id);
+            }
+        }
+    }
+
+    fn replay_journal(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let path = journal_path(dir);
+        if !path.exists() {
+            return Ok(());
+        }
+        for line in fs::read_to_string(path)?.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            self.apply_event(serde_json::from_str::<DbEvent>(line)?);
+        }
+        Ok(())
+    }
+
+    /// reconstruct a `Db` purely by folding every event in `journal` over an
+    /// empty `Db`, applied in file order. unlike `load_content_addressed`,
+    /// which only replays the tail after the newest manifest, this replays
+    /// the journal from scratch; useful when no snapshot exists yet, or for
+    /// debugging/inspecting the event history directly
+    pub fn replay(journal: &Path) -> LuaResult<Self> {
+        let mut db = Self::default();
+        let text = fs::read_to_string(journal).map_err(|e| {
+            println!("failed to open journal {:?}, {:?}", journal, e);
+            err("io error")
+        })?;
+        for line in text.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let ev = serde_json::from_str::<DbEvent>(line).map_err(|e| {
+                println!("failed to decode journal entry in {:?}, {:?}", journal, e);
+                err("decode error")
+            })?;
+            db.apply_event(ev);
+        }
+        Ok(db)
+    }
+
+    /// append a record to the journal so a crash between snapshots doesn't
+    /// lose it; called by the mutating methods' callers (`on_event`,
+    /// `Context::spawn_template_as_new`, `Context::respawn_groups`) right
+    /// after the corresponding `Db` mutation
+    pub fn journal_append(&self, dir: &Path, ev: &DbEvent) -> anyhow::Result<()> {
+        use std::io::Write;
+        fs::create_dir_all(dir)?;
+        let mut line = serde_json::to_string(ev)?;
+        line.push('\n');
+        let mut file = File::options()
+            .create(true)
+            .append(true)
+            .open(journal_path(dir))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
 }
\ No newline at end of file
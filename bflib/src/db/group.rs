@@ -14,11 +14,22 @@ FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero Public License
 for more details.
 */
 
-use std::collections::VecDeque;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt, fs, mem,
+    num::NonZeroU32,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    },
+    thread,
+};
 
 use super::{
     objective::{ObjGroupClass, ObjectiveId},
-    Db, Set,
+    Db, Map, Set,
 };
 use crate::{
     cfg::{Crate, Deployable, Troop, UnitTags},
@@ -31,7 +42,7 @@ use chrono::prelude::*;
 use compact_str::format_compact;
 use dcso3::{
     airbase::Airbase,
-    atomic_id, azumith3d, centroid2d,
+    azumith3d, centroid2d,
     coalition::Side,
     env::miz::{Group, GroupKind, MizIndex},
     group::GroupCategory,
@@ -49,8 +60,86 @@ use mlua::{prelude::*, Value};
 use serde_derive::{Deserialize, Serialize};
 use smallvec::{smallvec, SmallVec};
 
-atomic_id!(GroupId);
-atomic_id!(UnitId);
+/// a dense slot `index` paired with a `generation` counter that's bumped
+/// every time the slot is freed and handed back out, so a handle
+/// captured before a unit/group died and the slot was recycled for a
+/// new one fails the `persisted.units`/`persisted.groups` lookup instead
+/// of silently aliasing whatever now occupies that index. Unlike the
+/// plain `atomic_id!` ids used elsewhere, these two are the ones DCS
+/// object handles (`DcsOid`) get remapped onto across a respawn, so
+/// they're the ones worth hardening against reuse.
+macro_rules! generational_id {
+    ($name:ident, $free:ident, $next:ident) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        pub struct $name {
+            index: u32,
+            generation: NonZeroU32,
+        }
+
+        thread_local! {
+            static $free: RefCell<Vec<(u32, NonZeroU32)>> = RefCell::new(Vec::new());
+        }
+
+        static $next: AtomicU32 = AtomicU32::new(0);
+
+        impl $name {
+            pub fn new() -> Self {
+                $free
+                    .with(|free| free.borrow_mut().pop())
+                    .map(|(index, generation)| Self { index, generation })
+                    .unwrap_or_else(|| Self {
+                        index: $next.fetch_add(1, Ordering::Relaxed),
+                        generation: NonZeroU32::new(1).unwrap(),
+                    })
+            }
+
+            /// return this slot to the free list with its generation
+            /// bumped, so every handle issued for it up to now fails to
+            /// match whatever id gets `index` next
+            fn recycle(self) {
+                let next = self
+                    .generation
+                    .get()
+                    .checked_add(1)
+                    .and_then(NonZeroU32::new)
+                    .unwrap_or(NonZeroU32::new(1).unwrap());
+                $free.with(|free| free.borrow_mut().push((self.index, next)));
+            }
+
+            /// bump the fresh-allocation counter so it never hands out an
+            /// `index` less than `min_next`; called after [`Store::restore`]
+            /// reloads ids that were persisted by a previous process, so a
+            /// freshly spawned unit/group can't alias one of them at the
+            /// same index with `generation: 1`
+            fn reseed_next(min_next: u32) {
+                $next.fetch_max(min_next, Ordering::Relaxed);
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}g{}", self.index, self.generation)
+            }
+        }
+    };
+}
+
+generational_id!(GroupId, FREE_GROUP_IDS, NEXT_GROUP_ID);
+generational_id!(UnitId, FREE_UNIT_IDS, NEXT_UNIT_ID);
+
+/// fallback cell size for the `units_by_cell` spatial hash when
+/// `ephemeral.cfg` doesn't specify one; on the order of the largest
+/// relevant engagement radius so a `units_near` query only has to touch a
+/// handful of cells
+const DEFAULT_CELL_SIZE: f64 = 5_000.;
+
+fn cell_of(pos: Vector2, cell_size: f64) -> (i32, i32) {
+    // floored, not truncated, division so cells are contiguous across 0
+    (
+        (pos.x / cell_size).floor() as i32,
+        (pos.y / cell_size).floor() as i32,
+    )
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum DeployKind {
@@ -88,6 +177,11 @@ pub struct SpawnedUnit {
     pub dead: bool,
     #[serde(skip)]
     pub moved: Option<DateTime<Utc>>,
+    /// this unit's current key into `ephemeral.units_by_cell`, kept in
+    /// lockstep with `pos` so `update_unit_positions` can tell whether a
+    /// move actually crossed a cell boundary
+    #[serde(skip)]
+    pub cell: (i32, i32),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,7 +197,284 @@ pub struct SpawnedGroup {
     pub tags: UnitTags,
 }
 
+/// fired from `unit_dead` the moment a unit's health reaches zero, before
+/// `delete_group` is considered
+#[derive(Debug, Clone, Copy)]
+pub struct UnitDied {
+    pub uid: UnitId,
+    pub gid: GroupId,
+    pub last_pos: Vector2,
+}
+
+/// fired from `unit_dead` once a group has no living units left, before
+/// `delete_group` tears it down
+#[derive(Debug, Clone, Copy)]
+pub struct GroupDestroyed {
+    pub gid: GroupId,
+    pub survivors: usize,
+}
+
+/// whether a [`UnitDied`]/[`GroupDestroyed`] hook lets the teardown it was
+/// shown continue, or vetoes it; e.g. a "revive" handler that restores the
+/// unit's health can veto to keep `delete_group` from ever running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookDecision {
+    Allow,
+    Veto,
+}
+
+type DeathHook = Box<dyn FnMut(&mut Db, MizLua, &UnitDied) -> Result<HookDecision>>;
+type DestructionHook = Box<dyn FnMut(&mut Db, MizLua, &GroupDestroyed) -> Result<HookDecision>>;
+
+/// wrap a Lua function registered from script so it can sit in the same
+/// `death_hooks`/`destruction_hooks` lists as a native Rust hook; the
+/// function decides whether to veto by returning `false`, anything else
+/// (including nothing) allows the teardown to proceed
+fn lua_death_hook(key: mlua::RegistryKey) -> DeathHook {
+    Box::new(move |_db, lua, ev| {
+        let f: mlua::Function = lua.inner().registry_value(&key)?;
+        let ret: Value = f
+            .call((ev.uid.to_string(), ev.gid.to_string()))
+            .context("invoking scripted unit death hook")?;
+        Ok(if matches!(ret, Value::Boolean(false)) {
+            HookDecision::Veto
+        } else {
+            HookDecision::Allow
+        })
+    })
+}
+
+fn lua_destruction_hook(key: mlua::RegistryKey) -> DestructionHook {
+    Box::new(move |_db, lua, ev| {
+        let f: mlua::Function = lua.inner().registry_value(&key)?;
+        let ret: Value = f
+            .call((ev.gid.to_string(), ev.survivors))
+            .context("invoking scripted group destruction hook")?;
+        Ok(if matches!(ret, Value::Boolean(false)) {
+            HookDecision::Veto
+        } else {
+            HookDecision::Allow
+        })
+    })
+}
+
+/// a pending change to one of the LMDB-backed tables, batched up by id
+/// between flushes instead of written the instant it happens
+enum WriteOp {
+    PutUnit(UnitId, SpawnedUnit),
+    DeleteUnit(UnitId),
+    PutGroup(GroupId, SpawnedGroup),
+    DeleteGroup(GroupId),
+}
+
+/// write-behind LMDB store for `persisted.units`/`persisted.groups`: the
+/// in-memory maps remain the source of truth, this is just a durable log
+/// of them so a crash or restart doesn't lose the whole world. One
+/// `heed::Database` per domain, written in a single transaction per
+/// flush instead of rewriting everything `dirty()` marks as changed.
+pub struct Store {
+    env: heed::Env,
+    units: heed::Database<heed::types::SerdeBincode<UnitId>, heed::types::SerdeBincode<SpawnedUnit>>,
+    groups: heed::Database<heed::types::SerdeBincode<GroupId>, heed::types::SerdeBincode<SpawnedGroup>>,
+    tx: mpsc::Sender<Vec<WriteOp>>,
+}
+
+impl Store {
+    /// open (creating if necessary) the LMDB environment at `path` and
+    /// start the background writer thread that drains batches pushed by
+    /// [`Db::flush_to_store`]
+    pub fn open(path: &Path) -> Result<Self> {
+        fs::create_dir_all(path).context("creating store directory")?;
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(1024 * 1024 * 1024)
+                .max_dbs(2)
+                .open(path)
+                .context("opening lmdb environment")?
+        };
+        let mut wtxn = env.write_txn()?;
+        let units = env.create_database(&mut wtxn, Some("units"))?;
+        let groups = env.create_database(&mut wtxn, Some("groups"))?;
+        wtxn.commit()?;
+        let (tx, rx) = mpsc::channel::<Vec<WriteOp>>();
+        let writer_env = env.clone();
+        thread::spawn(move || {
+            while let Ok(batch) = rx.recv() {
+                let write = || -> Result<()> {
+                    let mut wtxn = writer_env.write_txn()?;
+                    for op in batch {
+                        match op {
+                            WriteOp::PutUnit(id, unit) => units.put(&mut wtxn, &id, &unit)?,
+                            WriteOp::DeleteUnit(id) => {
+                                units.delete(&mut wtxn, &id)?;
+                            }
+                            WriteOp::PutGroup(id, group) => groups.put(&mut wtxn, &id, &group)?,
+                            WriteOp::DeleteGroup(id) => {
+                                groups.delete(&mut wtxn, &id)?;
+                            }
+                        }
+                    }
+                    wtxn.commit()?;
+                    Ok(())
+                };
+                if let Err(e) = write() {
+                    error!("store writer failed to commit a batch, {:?}", e)
+                }
+            }
+        });
+        Ok(Self { env, units, groups, tx })
+    }
+
+    /// a consistent read-only view of the store, e.g. for an admin tool
+    /// to inspect state without racing the writer thread
+    pub fn snapshot(&self) -> Result<heed::RoTxn<'_>> {
+        Ok(self.env.read_txn()?)
+    }
+
+    /// rebuild `persisted.units`/`persisted.groups` from the store on
+    /// startup. Only those two maps are returned: `ephemeral.units_by_cell`
+    /// and each unit's `cell` are not rebuilt here. Engine
+    /// object handles in `object_id_by_uid`/`uid_by_object_id` are *not*
+    /// restored here: a `DcsOid` only names a live DCS object, and none
+    /// exist yet until the mission respawns units against this state
+    pub fn restore(&self) -> Result<(Map<UnitId, SpawnedUnit>, Map<GroupId, SpawnedGroup>)> {
+        let rtxn = self.env.read_txn()?;
+        let mut units = Map::new();
+        for res in self.units.iter(&rtxn)? {
+            let (id, unit) = res?;
+            UnitId::reseed_next(id.index.saturating_add(1));
+            units.insert_cow(id, unit);
+        }
+        let mut groups = Map::new();
+        for res in self.groups.iter(&rtxn)? {
+            let (id, group) = res?;
+            GroupId::reseed_next(id.index.saturating_add(1));
+            groups.insert_cow(id, group);
+        }
+        Ok((units, groups))
+    }
+}
+
 impl Db {
+    /// queue the units/groups `ephemeral` has marked dirty since the last
+    /// flush onto the store's background writer as a single batch,
+    /// instead of serializing the whole world every tick
+    pub fn flush_to_store(&mut self, store: &Store) -> Result<()> {
+        let dirty_units = mem::take(&mut self.ephemeral.dirty_units);
+        let deleted_units = mem::take(&mut self.ephemeral.deleted_units);
+        let dirty_groups = mem::take(&mut self.ephemeral.dirty_groups);
+        let deleted_groups = mem::take(&mut self.ephemeral.deleted_groups);
+        if dirty_units.is_empty()
+            && deleted_units.is_empty()
+            && dirty_groups.is_empty()
+            && deleted_groups.is_empty()
+        {
+            return Ok(());
+        }
+        let mut batch = Vec::with_capacity(
+            dirty_units.len() + deleted_units.len() + dirty_groups.len() + deleted_groups.len(),
+        );
+        for id in deleted_units {
+            batch.push(WriteOp::DeleteUnit(id));
+        }
+        for id in dirty_units {
+            if let Some(unit) = self.persisted.units.get(&id) {
+                batch.push(WriteOp::PutUnit(id, unit.clone()));
+            }
+        }
+        for id in deleted_groups {
+            batch.push(WriteOp::DeleteGroup(id));
+        }
+        for id in dirty_groups {
+            if let Some(group) = self.persisted.groups.get(&id) {
+                batch.push(WriteOp::PutGroup(id, group.clone()));
+            }
+        }
+        store
+            .tx
+            .send(batch)
+            .map_err(|_| anyhow!("store writer thread is gone"))
+    }
+
+    /// register a native hook to run on every [`UnitDied`] event, in
+    /// `ephemeral.death_hooks`, run in registration order until one vetoes
+    pub fn on_unit_died<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut Db, MizLua, &UnitDied) -> Result<HookDecision> + 'static,
+    {
+        self.ephemeral.death_hooks.push(Box::new(hook));
+    }
+
+    /// register a native hook to run on every [`GroupDestroyed`] event, in
+    /// `ephemeral.destruction_hooks`, run in registration order until one
+    /// vetoes
+    pub fn on_group_destroyed<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut Db, MizLua, &GroupDestroyed) -> Result<HookDecision> + 'static,
+    {
+        self.ephemeral.destruction_hooks.push(Box::new(hook));
+    }
+
+    /// register a scripted death hook; `f` is called as `f(uid, gid)` and
+    /// should return `false` to veto the pending teardown
+    pub fn on_unit_died_lua(&mut self, lua: MizLua, f: mlua::Function) -> Result<()> {
+        let key = lua.inner().create_registry_value(f)?;
+        self.ephemeral.death_hooks.push(lua_death_hook(key));
+        Ok(())
+    }
+
+    /// register a scripted group-destruction hook; `f` is called as
+    /// `f(gid, survivors)` and should return `false` to veto
+    pub fn on_group_destroyed_lua(&mut self, lua: MizLua, f: mlua::Function) -> Result<()> {
+        let key = lua.inner().create_registry_value(f)?;
+        self.ephemeral.destruction_hooks.push(lua_destruction_hook(key));
+        Ok(())
+    }
+
+    /// run every registered death hook for `ev`, stopping at (and
+    /// returning) the first veto
+    fn run_death_hooks(&mut self, lua: MizLua, ev: &UnitDied) -> Result<HookDecision> {
+        let mut hooks = std::mem::take(&mut self.ephemeral.death_hooks);
+        let mut result = Ok(HookDecision::Allow);
+        for hook in hooks.iter_mut() {
+            match hook(self, lua, ev) {
+                Ok(HookDecision::Veto) => {
+                    result = Ok(HookDecision::Veto);
+                    break;
+                }
+                Ok(HookDecision::Allow) => (),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.ephemeral.death_hooks = hooks;
+        result
+    }
+
+    /// run every registered group-destruction hook for `ev`, stopping at
+    /// (and returning) the first veto
+    fn run_destruction_hooks(&mut self, lua: MizLua, ev: &GroupDestroyed) -> Result<HookDecision> {
+        let mut hooks = std::mem::take(&mut self.ephemeral.destruction_hooks);
+        let mut result = Ok(HookDecision::Allow);
+        for hook in hooks.iter_mut() {
+            match hook(self, lua, ev) {
+                Ok(HookDecision::Veto) => {
+                    result = Ok(HookDecision::Veto);
+                    break;
+                }
+                Ok(HookDecision::Allow) => (),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.ephemeral.destruction_hooks = hooks;
+        result
+    }
+
     pub fn groups(&self) -> impl Iterator<Item = (&GroupId, &SpawnedGroup)> {
         self.persisted.groups.into_iter()
     }
@@ -252,9 +623,17 @@ impl Db {
             }
             if let Some(unit) = self.persisted.units.remove_cow(uid) {
                 self.persisted.units_by_name.remove_cow(&unit.name);
+                if let Some(bucket) = self.ephemeral.units_by_cell.get_mut(&unit.cell) {
+                    bucket.retain(|u| u != uid);
+                }
+                self.ephemeral.dirty_units.remove(uid);
+                self.ephemeral.deleted_units.insert(*uid);
                 units.push(unit.name);
+                unit.id.recycle();
             }
         }
+        self.ephemeral.dirty_groups.remove(gid);
+        self.ephemeral.deleted_groups.insert(*gid);
         self.ephemeral.dirty();
         match group.kind {
             None => {
@@ -270,6 +649,7 @@ impl Db {
                     .push_despawn(*gid, Despawn::Group(group.name.clone()));
             }
         }
+        group.id.recycle();
         Ok(())
     }
 
@@ -460,6 +840,8 @@ impl Db {
                 p.p.z = pos.y;
                 p
             };
+            let cell_size = self.ephemeral.cfg.proximity_cell_size.unwrap_or(DEFAULT_CELL_SIZE);
+            let cell = cell_of(pos, cell_size);
             let spawned_unit = SpawnedUnit {
                 id: uid,
                 group: gid,
@@ -476,10 +858,17 @@ impl Db {
                 heading,
                 dead: false,
                 moved: None,
+                cell,
             };
             spawned.units.insert_cow(uid);
             self.persisted.units.insert_cow(uid, spawned_unit);
             self.persisted.units_by_name.insert_cow(unit_name, uid);
+            self.ephemeral
+                .units_by_cell
+                .entry(cell)
+                .or_insert_with(SmallVec::new)
+                .push(uid);
+            self.ephemeral.dirty_units.insert(uid);
         }
         match &mut spawned.origin {
             DeployKind::Objective => (),
@@ -509,6 +898,7 @@ impl Db {
             .groups_by_side
             .get_or_default_cow(side)
             .insert_cow(gid);
+        self.ephemeral.dirty_groups.insert(gid);
         self.ephemeral.dirty();
         self.mark_group(&gid)?;
         Ok(gid)
@@ -614,23 +1004,52 @@ impl Db {
                 uid
             }
         };
-        match self.persisted.units.get_mut_cow(&uid) {
-            None => error!("unit_dead: missing unit {:?}", uid),
+        let died = match self.persisted.units.get_mut_cow(&uid) {
+            None => {
+                error!("unit_dead: missing unit {:?}", uid);
+                None
+            }
             Some(unit) => {
+                if let Some(bucket) = self.ephemeral.units_by_cell.get_mut(&unit.cell) {
+                    bucket.retain(|u| *u != uid);
+                }
+                let last_pos = unit.pos;
                 unit.dead = true;
                 unit.pos = unit.spawn_pos;
                 unit.heading = unit.spawn_heading;
                 unit.position = unit.spawn_position;
+                self.ephemeral.dirty_units.insert(uid);
                 self.ephemeral.dirty();
-                let gid = unit.group;
-                if let Some(oid) = self.persisted.objectives_by_group.get(&gid).copied() {
-                    self.update_objective_status(&oid, now)?
-                }
-                if self.persisted.deployed.contains(&gid)
-                    || self.persisted.troops.contains(&gid)
-                    || self.persisted.crates.contains(&gid)
-                {
-                    if self.group_health(&gid)?.0 == 0 {
+                Some(UnitDied {
+                    uid,
+                    gid: unit.group,
+                    last_pos,
+                })
+            }
+        };
+        if let Some(ev) = died {
+            let gid = ev.gid;
+            if let Some(oid) = self.persisted.objectives_by_group.get(&gid).copied() {
+                self.update_objective_status(&oid, now)?
+            }
+            // a death hook gets a chance to "revive" the unit (e.g. flip
+            // `dead` back to `false`) before we go looking at whether its
+            // group is now empty; a veto here skips the destruction check
+            // entirely for this death
+            if self.run_death_hooks(lua, &ev)? == HookDecision::Veto {
+                return Ok(());
+            }
+            if self.persisted.deployed.contains(&gid)
+                || self.persisted.troops.contains(&gid)
+                || self.persisted.crates.contains(&gid)
+            {
+                let (alive, _) = self.group_health(&gid)?;
+                if alive == 0 {
+                    let destroyed = GroupDestroyed {
+                        gid,
+                        survivors: alive,
+                    };
+                    if self.run_destruction_hooks(lua, &destroyed)? == HookDecision::Allow {
                         self.delete_group(&gid)?
                     }
                 }
@@ -650,21 +1069,46 @@ impl Db {
         Ok((alive, group.units.len()))
     }
 
-    pub fn update_unit_positions<'a, I: Iterator<Item = UnitId> + 'a>(
-        &'a mut self,
+    /// bucket `units` by the group that owns them, in encounter order,
+    /// so a caller can drive per-group work (e.g. [`Self::refresh_group`])
+    /// a whole group at a time instead of hopping between unrelated
+    /// groups on every iteration
+    pub fn iter_units_by_group<I: Iterator<Item = UnitId>>(
+        &self,
+        units: I,
+    ) -> Vec<(GroupId, SmallVec<[UnitId; 8]>)> {
+        let mut by_group: FxHashMap<GroupId, SmallVec<[UnitId; 8]>> = FxHashMap::default();
+        let mut order: SmallVec<[GroupId; 16]> = smallvec![];
+        for uid in units {
+            let Some(unit) = self.persisted.units.get(&uid) else {
+                continue;
+            };
+            let bucket = by_group.entry(unit.group).or_insert_with(|| {
+                order.push(unit.group);
+                smallvec![]
+            });
+            bucket.push(uid);
+        }
+        order
+            .into_iter()
+            .filter_map(|gid| by_group.remove(&gid).map(|uids| (gid, uids)))
+            .collect()
+    }
+
+    /// refresh the live position of every unit in `uids` (all belonging
+    /// to `gid`), reusing one `Unit` instance across the whole group via
+    /// `change_instance` to cut down on Lua round-trips, and mark `gid`
+    /// dirty exactly once if anything moved
+    fn refresh_group(
+        &mut self,
         lua: MizLua,
-        units: Option<I>,
-    ) -> Result<Vec<DcsOid<ClassUnit>>> {
+        gid: GroupId,
+        uids: &[UnitId],
+        dead: &mut Vec<DcsOid<ClassUnit>>,
+    ) -> Result<()> {
         let mut unit: Option<Unit> = None;
-        let mut moved: SmallVec<[GroupId; 16]> = smallvec![];
-        let mut dead: Vec<DcsOid<ClassUnit>> = vec![];
-        let units = units
-            .map(|i| Box::new(i) as Box<dyn Iterator<Item = UnitId>>)
-            .unwrap_or_else(|| {
-                Box::new(self.ephemeral.units_able_to_move.iter().map(|i| *i))
-                    as Box<dyn Iterator<Item = UnitId>>
-            });
-        for uid in units {
+        let mut moved = false;
+        for &uid in uids {
             let id = match self.ephemeral.object_id_by_uid.get(&uid) {
                 Some(id) => id,
                 None => {
@@ -692,21 +1136,81 @@ impl Db {
             let heading = azumith3d(pos.x.0);
             let spunit = unit_mut!(self, uid)?;
             if spunit.position != pos {
-                moved.push(spunit.group);
+                moved = true;
                 spunit.position = pos;
                 spunit.pos = point;
                 spunit.heading = heading;
-                self.ephemeral
-                    .units_potentially_close_to_enemies
-                    .insert(uid);
+                self.ephemeral.dirty_units.insert(uid);
+                let cell_size = self.ephemeral.cfg.proximity_cell_size.unwrap_or(DEFAULT_CELL_SIZE);
+                let cell = cell_of(point, cell_size);
+                if cell != spunit.cell {
+                    if let Some(bucket) = self.ephemeral.units_by_cell.get_mut(&spunit.cell) {
+                        bucket.retain(|u| *u != uid);
+                    }
+                    self.ephemeral
+                        .units_by_cell
+                        .entry(cell)
+                        .or_insert_with(SmallVec::new)
+                        .push(uid);
+                    spunit.cell = cell;
+                    // only units whose grid membership actually changed are
+                    // candidates for a changed proximity-to-enemy state
+                    self.ephemeral
+                        .units_potentially_close_to_enemies
+                        .insert(uid);
+                }
                 self.ephemeral.units_potentially_on_walkabout.insert(uid);
             }
             unit = Some(instance);
         }
-        for gid in moved {
+        if moved {
+            self.ephemeral.dirty_groups.insert(gid);
             self.ephemeral.dirty();
             self.mark_group(&gid)?;
         }
+        Ok(())
+    }
+
+    pub fn update_unit_positions<'a, I: Iterator<Item = UnitId> + 'a>(
+        &'a mut self,
+        lua: MizLua,
+        units: Option<I>,
+    ) -> Result<Vec<DcsOid<ClassUnit>>> {
+        let units = units
+            .map(|i| Box::new(i) as Box<dyn Iterator<Item = UnitId>>)
+            .unwrap_or_else(|| {
+                Box::new(self.ephemeral.units_able_to_move.iter().map(|i| *i))
+                    as Box<dyn Iterator<Item = UnitId>>
+            });
+        let mut dead: Vec<DcsOid<ClassUnit>> = vec![];
+        for (gid, uids) in self.iter_units_by_group(units) {
+            self.refresh_group(lua, gid, &uids, &mut dead)?;
+        }
         Ok(dead)
     }
+
+    /// every live unit within `radius` meters of `center`, found by
+    /// scanning only the `units_by_cell` cells the query's bounding box
+    /// overlaps and filtering by true Euclidean distance, instead of a
+    /// brute-force scan of every spawned unit
+    pub fn units_near<'a>(
+        &'a self,
+        center: Vector2,
+        radius: f64,
+    ) -> impl Iterator<Item = UnitId> + 'a {
+        let cell_size = self.ephemeral.cfg.proximity_cell_size.unwrap_or(DEFAULT_CELL_SIZE);
+        let (cx, cy) = cell_of(center, cell_size);
+        let span = (radius / cell_size).ceil() as i32;
+        let r2 = radius * radius;
+        (-span..=span)
+            .flat_map(move |dx| (-span..=span).map(move |dy| (cx + dx, cy + dy)))
+            .filter_map(move |cell| self.ephemeral.units_by_cell.get(&cell))
+            .flat_map(|bucket| bucket.iter().copied())
+            .filter(move |uid| {
+                self.persisted.units.get(uid).is_some_and(|u| {
+                    let d = u.pos - center;
+                    d.x * d.x + d.y * d.y <= r2
+                })
+            })
+    }
 }
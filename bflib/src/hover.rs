@@ -0,0 +1,188 @@
+use crate::{
+    db::cargo::SlotStats,
+    menu,
+    Context,
+};
+use anyhow::Result;
+use compact_str::format_compact;
+use dcso3::{coalition::Side, env::miz::GroupId, net::SlotId, MizLua};
+use fxhash::{FxHashMap, FxHashSet};
+
+/// thresholds for the hover pickup/drop state machine; `enabled` is the
+/// `hover_pickup` config flag, the rest mirror the distance/altitude/speed/
+/// hold-time knobs CTLD-style logistics mods expose
+#[derive(Debug, Clone, Copy)]
+pub struct HoverCfg {
+    pub enabled: bool,
+    pub radius: f64,
+    pub alt: f64,
+    pub speed: f64,
+    pub hold_time: f64,
+}
+
+impl Default for HoverCfg {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 15.,
+            alt: 12.,
+            speed: 2.,
+            hold_time: 5.,
+        }
+    }
+}
+
+/// the time each slot began holding a qualifying hover (`hold`),
+/// plus the set of slots already checked for a preloaded starting
+/// loadout (`preloaded`); both are dropped for a slot the instant its
+/// aircraft is no longer live, so the next occupant starts from scratch
+#[derive(Debug, Default)]
+pub struct HoverState {
+    hold: FxHashMap<SlotId, f64>,
+    preloaded: FxHashSet<SlotId>,
+}
+
+impl HoverState {
+    /// walk every currently occupied player slot and load or unload the
+    /// nearest crate for whichever ones have held a qualifying hover for
+    /// `cfg.hold_time` seconds; called once per tick from the same kind of
+    /// `Timer::schedule_function` loop the rest of the mission uses for
+    /// periodic work
+    pub fn tick(&mut self, lua: MizLua, cfg: &HoverCfg, now: f64) -> Result<()> {
+        let ctx = unsafe { Context::get_mut() };
+        let mut live = FxHashMap::default();
+        for (side, gid, slot) in ctx.db.active_player_slots() {
+            live.insert(slot.clone(), ());
+            // logistics-zone smoke and the starting loadout are both
+            // independent of hover pickup/drop, so they still run even
+            // when `hover_pickup` itself is turned off
+            if let Err(e) = self.tick_auto_smoke(lua, ctx, gid, &slot) {
+                ctx.db.msgs().panel_to_group(10, false, gid, format_compact!("{}", e));
+            }
+            if let Err(e) = self.tick_preload_troops(lua, ctx, side, gid, &slot) {
+                ctx.db.msgs().panel_to_group(10, false, gid, format_compact!("{}", e));
+            }
+            if cfg.enabled {
+                if let Err(e) = self.tick_slot(lua, ctx, cfg, now, side, gid, &slot) {
+                    self.hold.remove(&slot);
+                    ctx.db.msgs().panel_to_group(10, false, gid, format_compact!("{}", e));
+                }
+            }
+        }
+        // a slot whose aircraft left (landed at a FARP, died, disconnected)
+        // stops accruing progress, and is eligible for a fresh preload
+        // check, instead of lingering in the maps forever
+        self.hold.retain(|slot, _| live.contains_key(slot));
+        self.preloaded.retain(|slot| live.contains_key(slot));
+        Ok(())
+    }
+
+    /// populate a newly occupied slot with its `cfg.preload_troops`
+    /// default squad, the first tick it's seen; a no-op on every later
+    /// tick because `self.preloaded` already has the slot, and a no-op
+    /// if the slot already carries cargo (e.g. a respawned group that
+    /// kept a previous occupant's manifest) so a player never gets a
+    /// second free squad on top of one already aboard
+    fn tick_preload_troops(
+        &mut self,
+        lua: MizLua,
+        ctx: &mut Context,
+        side: Side,
+        gid: GroupId,
+        slot: &SlotId,
+    ) -> Result<()> {
+        if !self.preloaded.insert(slot.clone()) {
+            return Ok(());
+        }
+        if ctx
+            .db
+            .list_cargo(slot)
+            .map(|c| c.num_total() > 0)
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        let uinfo = ctx.db.slot_miz_unit(lua, &ctx.idx, slot)?;
+        let typ = uinfo.unit.typ()?;
+        let squad = match ctx
+            .db
+            .cfg()
+            .preload_troops
+            .get(&side)
+            .and_then(|by_typ| by_typ.get(typ.as_str()))
+        {
+            Some(squad) => squad.clone(),
+            None => return Ok(()),
+        };
+        let (tr, taken) = ctx.db.load_troops(lua, &ctx.idx, slot, &squad, u32::MAX)?;
+        ctx.db.msgs().panel_to_group(
+            10,
+            false,
+            gid,
+            format_compact!("starting loadout: {taken} {} aboard", tr.name),
+        );
+        menu::refresh_cargo_mass(lua, ctx, slot)?;
+        menu::list_cargo_for_slot(lua, ctx, slot)
+    }
+
+    /// pop a logistics-area smoke marker the moment a transport enters it;
+    /// `disable_all_smoke` is rechecked here (not just at menu-build time)
+    /// so toggling it mid-mission takes effect immediately, and cooldown/
+    /// already-smoked-this-visit bookkeeping lives in `Db` alongside the
+    /// rest of the cargo state it already owns
+    fn tick_auto_smoke(&self, lua: MizLua, ctx: &mut Context, gid: GroupId, slot: &SlotId) -> Result<()> {
+        if ctx.db.cfg().disable_all_smoke {
+            return Ok(());
+        }
+        if let Some(msg) = ctx.db.smoke_logistics_zone(lua, &ctx.idx, slot)? {
+            ctx.db.msgs().panel_to_group(10, false, gid, msg);
+        }
+        Ok(())
+    }
+
+    fn tick_slot(
+        &mut self,
+        lua: MizLua,
+        ctx: &mut Context,
+        cfg: &HoverCfg,
+        now: f64,
+        _side: Side,
+        gid: GroupId,
+        slot: &SlotId,
+    ) -> Result<()> {
+        let st = SlotStats::get(&ctx.db, lua, slot)?;
+        let carrying = ctx
+            .db
+            .list_cargo(slot)
+            .map(|c| c.num_crates() > 0)
+            .unwrap_or(false);
+        let nearby = if carrying {
+            None
+        } else {
+            ctx.db.list_nearby_crates(&st)?.into_iter().next()
+        };
+        let hovering = st.agl <= cfg.alt
+            && st.speed <= cfg.speed
+            && (carrying || nearby.map(|nc| nc.distance <= cfg.radius).unwrap_or(false));
+        if !hovering {
+            self.hold.remove(slot);
+            return Ok(());
+        }
+        let started = *self.hold.entry(slot.clone()).or_insert(now);
+        if now - started < cfg.hold_time {
+            return Ok(());
+        }
+        self.hold.remove(slot);
+        if carrying {
+            let cr = ctx.db.unload_crate(lua, &ctx.idx, slot)?;
+            let msg = format_compact!("{} crate unloaded (hover drop)", cr.name);
+            ctx.db.msgs().panel_to_group(10, false, gid, msg)
+        } else {
+            let cr = ctx.db.load_nearby_crate(lua, &ctx.idx, slot)?;
+            let msg = format_compact!("{} crate loaded (hover pickup)", cr.name);
+            ctx.db.msgs().panel_to_group(10, false, gid, msg)
+        }
+        menu::refresh_cargo_mass(lua, ctx, slot)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,12 @@
+pub mod chat;
 pub mod db;
+pub mod plan;
+pub mod players;
 extern crate nalgebra as na;
+use chat::Commands;
 use compact_str::format_compact;
 use db::{Db, GroupId, SpawnLoc, UnitId};
+use players::{PlayerId, Players};
 use dcso3::{
     coalition::Side,
     env::{
@@ -13,9 +18,10 @@ use dcso3::{
     event::Event,
     lfs::Lfs,
     timer::Timer,
-    world::World,
+    world::{Control, World},
     wrap_unit, String, UserHooks, Vector2,
 };
+use enumflags2::BitFlags;
 use fxhash::FxHashMap;
 use mlua::prelude::*;
 use std::{path::PathBuf, sync::mpsc, thread};
@@ -24,9 +30,35 @@ use std::{path::PathBuf, sync::mpsc, thread};
 enum BgTask {
     MizInit,
     SaveState(PathBuf, Db),
+    /// periodic compaction: a full content addressed snapshot, which also
+    /// truncates the journal since everything in it is now captured
+    SaveSnapshot(PathBuf, Db),
+    /// ship a single event's JSON payload to the configured webhook; never
+    /// runs on the scripting thread so a slow or down endpoint can't stall
+    /// the sim
+    PostEvent(std::string::String, serde_json::Value),
+}
+
+/// how many delivery attempts to make in total, and the base of the
+/// exponential backoff between attempts, before giving up on an event
+/// rather than blocking the worker forever
+const POST_RETRIES: u32 = 5;
+const POST_BACKOFF: std::time::Duration = std::time::Duration::from_millis(250);
+
+fn post_event(client: &reqwest::blocking::Client, url: &str, payload: &serde_json::Value) {
+    for attempt in 0..POST_RETRIES {
+        match client.post(url).json(payload).send() {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => println!("telemetry post to {} returned {}", url, resp.status()),
+            Err(e) => println!("telemetry post to {} failed, {:?}", url, e),
+        }
+        thread::sleep(POST_BACKOFF * 2u32.pow(attempt));
+    }
+    println!("giving up on telemetry event after {} attempts", POST_RETRIES);
 }
 
 fn background_loop(rx: mpsc::Receiver<BgTask>) {
+    let http = reqwest::blocking::Client::new();
     while let Ok(msg) = rx.recv() {
         match msg {
             BgTask::MizInit => (),
@@ -34,6 +66,11 @@ fn background_loop(rx: mpsc::Receiver<BgTask>) {
                 Ok(()) => (),
                 Err(e) => println!("failed to save state to {:?}, {:?}", path, e),
             },
+            BgTask::SaveSnapshot(dir, db) => match db.save_content_addressed(&dir) {
+                Ok(()) => (),
+                Err(e) => println!("failed to snapshot state to {:?}, {:?}", dir, e),
+            },
+            BgTask::PostEvent(url, payload) => post_event(&http, &url, &payload),
         }
     }
 }
@@ -44,6 +81,17 @@ struct Context {
     db: Db,
     to_background: Option<mpsc::Sender<BgTask>>,
     units_by_obj_id: FxHashMap<i64, UnitId>,
+    /// directory holding the content addressed blob store and root
+    /// manifests; derived from the save `path` the first time it's needed
+    snapshot_dir: Option<PathBuf>,
+    players: Players,
+    players_path: Option<PathBuf>,
+    /// maps the short lived numeric player id the server hands hooks like
+    /// `onPlayerTryChangeSlot` to the durable `PlayerId` derived from ucid
+    net_id_to_player: FxHashMap<u32, PlayerId>,
+    chat_commands: Commands,
+    relations: dcso3::coalition::Relations,
+    telemetry_url: Option<std::string::String>,
 }
 
 static mut CONTEXT: Option<Context> = None;
@@ -60,7 +108,9 @@ impl Context {
                 Some(ctx) => ctx,
                 None => {
                     println!("init ctx");
-                    CONTEXT = Some(Context::default());
+                    let mut ctx = Context::default();
+                    chat::register_builtins(&mut ctx.chat_commands);
+                    CONTEXT = Some(ctx);
                     CONTEXT.as_mut().unwrap()
                 }
             }
@@ -91,17 +141,47 @@ impl Context {
         location: &SpawnLoc,
         template_name: &str,
     ) -> LuaResult<GroupId> {
-        self.db
-            .spawn_template_as_new(lua, &self.idx, side, kind, location, template_name)
+        let gid =
+            self.db
+                .spawn_template_as_new(lua, &self.idx, side, kind, location, template_name)?;
+        if let Some(dir) = self.snapshot_dir.clone() {
+            if let Some(group) = self.db.get_group(&gid).cloned() {
+                if let Err(e) = self.db.journal_append(&dir, &db::DbEvent::GroupSpawned(group.clone())) {
+                    println!("failed to journal spawn of {:?}, {:?}", gid, e);
+                }
+                for uid in &group.units {
+                    if let Some(unit) = self.db.get_unit(uid).cloned() {
+                        if let Err(e) = self.db.journal_append(&dir, &db::DbEvent::UnitSpawned(unit)) {
+                            println!("failed to journal spawn of {:?}, {:?}", uid, e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(gid)
     }
 
     fn respawn_groups(&mut self, lua: &Lua) -> LuaResult<()> {
         let spctx = db::SpawnCtx::new(lua)?;
+        let dir = self.snapshot_dir.clone();
         for (_, group) in self.db.groups() {
-            self.db.respawn_group(&self.idx, &spctx, group)?
+            if self.db.respawn_group(&self.idx, &spctx, group)? {
+                if let Some(dir) = &dir {
+                    if let Err(e) = self
+                        .db
+                        .journal_append(dir, &db::DbEvent::GroupSpawned(group.clone()))
+                    {
+                        println!("failed to journal respawn of {:?}, {:?}", group.id, e);
+                    }
+                }
+            }
         }
         Ok(())
     }
+
+    fn players_id_for_net_id(&self, id: u32) -> Option<PlayerId> {
+        self.net_id_to_player.get(&id).copied()
+    }
 }
 
 fn on_player_try_connect(
@@ -115,15 +195,40 @@ fn on_player_try_connect(
         "onPlayerTryConnect addr: {:?}, name: {:?}, ucid: {:?}, id: {:?}",
         addr, name, ucid, id
     );
-    Ok(true)
+    let ctx = Context::get_mut();
+    match ctx.players.try_connect(ucid.as_str(), name.as_str()) {
+        Ok(pid) => {
+            ctx.net_id_to_player.insert(id, pid);
+            if let Some(path) = ctx.players_path.clone() {
+                if let Err(e) = ctx.players.save(&path) {
+                    println!("failed to save players to {:?}, {:?}", path, e)
+                }
+            }
+            Ok(true)
+        }
+        Err(reason) => {
+            println!("rejecting connection from {:?}: {}", ucid, reason);
+            Ok(false)
+        }
+    }
 }
 
-fn on_player_try_send_chat(_: &Lua, id: u32, msg: String, all: bool) -> LuaResult<String> {
+fn on_player_try_send_chat(lua: &Lua, id: u32, msg: String, all: bool) -> LuaResult<String> {
     println!(
         "onPlayerTrySendChat id: {:?}, msg: {:?}, all: {:?}",
         id, msg, all
     );
-    Ok(msg)
+    let ctx = Context::get_mut();
+    // take the commands table out so built-in handlers, which take `&mut
+    // Context`, aren't fighting an outstanding borrow of it
+    let cmds = std::mem::take(&mut ctx.chat_commands);
+    let recognized = cmds.dispatch(ctx, lua, id, msg.as_str());
+    ctx.chat_commands = cmds;
+    if recognized {
+        Ok(String::from(""))
+    } else {
+        Ok(msg)
+    }
 }
 
 fn on_player_try_change_slot(_: &Lua, id: u32, side: Side, slot: String) -> LuaResult<bool> {
@@ -131,34 +236,62 @@ fn on_player_try_change_slot(_: &Lua, id: u32, side: Side, slot: String) -> LuaR
         "onPlayerTryChangeSlot id: {:?}, side: {:?}, slot: {:?}",
         id, side, slot
     );
-    Ok(true)
+    let ctx = Context::get_mut();
+    // the server only hands us the numeric player id here, not the ucid, so
+    // look the player up the same way `unit_born` does for in game units
+    let pid = match ctx.players_id_for_net_id(id) {
+        Some(pid) => pid,
+        None => return Ok(true),
+    };
+    match ctx.players.try_change_slot(pid, side, slot.as_str()) {
+        Ok(()) => Ok(true),
+        Err(reason) => {
+            println!("denying slot change for player {}: {}", id, reason);
+            Ok(false)
+        }
+    }
 }
 
-fn on_event(_lua: &Lua, ev: Event) -> LuaResult<()> {
+fn on_event(_lua: &Lua, ev: &Event) -> LuaResult<Control> {
     println!("onEventTranslated: {:?}", ev);
     let ctx = Context::get_mut();
+    if let Some(url) = ctx.telemetry_url.clone() {
+        if let Ok(payload) = serde_json::to_value(ev) {
+            ctx.do_background_task(BgTask::PostEvent(url, payload));
+        }
+    }
     match ev {
         Event::Birth(b) => {
-            if let Ok(unit) = b.initiator.as_unit() {
-                let name = unit.as_object()?.get_name()?;
-                if let Some(su) = ctx.db.get_unit_by_name(name.as_str()) {
-                    let uid = su.id;
-                    let oid: i64 = unit.get_object_id()?;
-                    ctx.units_by_obj_id.insert(oid, uid);
+            let name = b.initiator.as_object()?.get_name()?;
+            if let Some(su) = ctx.db.get_unit_by_name(name.as_str()).cloned() {
+                let uid = su.id;
+                let oid: i64 = b.initiator.get_object_id()?;
+                ctx.units_by_obj_id.insert(oid, uid);
+                if let Some(dir) = &ctx.snapshot_dir {
+                    if let Err(e) = ctx.db.journal_append(dir, &db::DbEvent::UnitSpawned(su)) {
+                        println!("failed to journal birth of {:?}, {:?}", uid, e);
+                    }
                 }
             }
         }
         Event::Dead(e) => {
-            if let Ok(unit) = e.initiator.as_unit() {
-                let id = unit.get_object_id()?;
-                if let Some(uid) = ctx.units_by_obj_id.remove(&id) {
-                    ctx.db.unit_dead(uid, true);
+            let id = e.initiator.get_object_id()?;
+            if let Some(uid) = ctx.units_by_obj_id.remove(&id) {
+                ctx.db.unit_dead(uid, true);
+                // best effort: a destroyed unit's cargo mass no longer
+                // matters to the sim, but this keeps the handle from
+                // briefly reporting stale added weight if it's reused
+                let _ = e.initiator.set_cargo_mass(0.);
+                if let Some(dir) = &ctx.snapshot_dir {
+                    if let Err(e) = ctx.db.journal_append(dir, &db::DbEvent::UnitDead { id: uid }) {
+                        println!("failed to journal death of {:?}, {:?}", uid, e);
+                    }
                 }
             }
         }
         _ => (),
     }
-    Ok(())
+    Ok(Control::Continue)
 }
 
 fn on_mission_load_end(lua: &Lua) -> LuaResult<()> {
@@ -221,13 +354,53 @@ fn spawn_new(lua: &Lua, ctx: &mut Context) -> LuaResult<()> {
 fn init_miz_(lua: &Lua) -> LuaResult<()> {
     let ctx = Context::get_mut();
     println!("adding event handler");
-    World::get(lua)?.add_event_handler(on_event)?;
+    World::get(lua)?.events().subscribe(BitFlags::all(), on_event)?;
     let sortie = Miz::singleton(lua)?.sortie()?;
     let path = match Env::singleton(lua)?.get_value_dict_by_key(sortie)?.as_str() {
         "" => return Err(err("missing sortie in miz file")),
         s => PathBuf::from(format_compact!("{}\\{}", Lfs::singleton(lua)?.writedir()?, s).as_str()),
     };
+    let mut snapshot_dir = path.clone();
+    snapshot_dir.set_extension("snapshot");
+    ctx.snapshot_dir = Some(snapshot_dir.clone());
+    let mut players_path = path.clone();
+    players_path.set_extension("players.json");
+    ctx.players = Players::load(&players_path).map_err(|e| {
+        println!("failed to load players from {:?}, {:?}", players_path, e);
+        err("decode error")
+    })?;
+    ctx.players_path = Some(players_path);
+    // the relationship matrix is optional; a mission that doesn't define
+    // one gets the default Blue/Red + Merc-vs-Green relations
+    let relations_key = Env::singleton(lua)?.get_value_dict_by_key("relations")?;
+    ctx.relations = match relations_key.as_str() {
+        "" => dcso3::coalition::Relations::default(),
+        s => serde_json::from_str(s).map_err(|e| {
+            println!("failed to parse relations matrix, {:?}", e);
+            err("invalid relations matrix")
+        })?,
+    };
+    ctx.relations
+        .validate()
+        .map_err(|e| {
+            println!("mission relations matrix is not symmetric, {:?}", e);
+            err("asymmetric relations matrix")
+        })?;
+    ctx.telemetry_url = match Env::singleton(lua)?
+        .get_value_dict_by_key("telemetry_url")?
+        .as_str()
+    {
+        "" => None,
+        url => Some(std::string::String::from(url)),
+    };
     let timer = Timer::singleton(lua)?;
+    // rescheduling for `now` rather than `now + dt` is the usual DCS idiom
+    // for "run again next frame"; this is what drives any event handler
+    // registered with `EventBus::subscribe_async` forward
+    timer.schedule_function(timer.get_time()?, mlua::Value::Nil, |lua, _, now| {
+        World::get(lua)?.poll_handlers();
+        Ok(Some(now))
+    })?;
     timer.schedule_function(timer.get_time()? + 10., mlua::Value::Nil, {
         let path = path.clone();
         move |_lua, _, now| {
@@ -238,8 +411,21 @@ fn init_miz_(lua: &Lua) -> LuaResult<()> {
             Ok(Some(now + 10.))
         }
     })?;
+    // periodic compaction: fold everything the journal has accumulated
+    // since the last snapshot into a fresh root manifest and truncate it
+    timer.schedule_function(timer.get_time()? + 60., mlua::Value::Nil, {
+        let dir = snapshot_dir.clone();
+        move |_lua, _, now| {
+            let ctx = Context::get_mut();
+            ctx.do_background_task(BgTask::SaveSnapshot(dir.clone(), ctx.db.clone()));
+            Ok(Some(now + 60.))
+        }
+    })?;
     println!("spawning");
-    if !path.exists() {
+    if snapshot_dir.exists() {
+        ctx.db = Db::load_content_addressed(&snapshot_dir)?;
+        ctx.respawn_groups(lua)?
+    } else if !path.exists() {
         spawn_new(lua, ctx)?;
     } else {
         ctx.db = Db::load(&path)?;
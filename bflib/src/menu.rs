@@ -24,6 +24,7 @@ use enumflags2::{BitFlag, BitFlags};
 use fxhash::FxHashMap;
 use log::debug;
 use mlua::{prelude::*, Value};
+use serde_derive::Deserialize;
 use std::collections::hash_map::Entry;
 
 #[derive(Debug)]
@@ -81,14 +82,52 @@ fn player_name(db: &Db, slot: &SlotId) -> String {
         .unwrap_or_default()
 }
 
+/// `Some(msg)` when `slot` is within `cfg.min_logistics_distance` of an
+/// existing friendly objective, FARP, or deployed logistics point, in
+/// which case the caller should reject the spawn/unpack instead of
+/// stacking another one on top of it
+fn too_close_to_logistics(
+    ctx: &Context,
+    lua: MizLua,
+    side: Side,
+    slot: &SlotId,
+) -> Result<Option<CompactString>> {
+    let min = match ctx.db.cfg().min_logistics_distance {
+        Some(min) => min,
+        None => return Ok(None),
+    };
+    let st = SlotStats::get(&ctx.db, lua, slot)?;
+    Ok(match ctx.db.nearest_friendly_logistics_distance(side, &st) {
+        Some(d) if d < min => Some(format_compact!(
+            "too close to friendly logistics, move {} m away",
+            min as u32
+        )),
+        Some(_) | None => None,
+    })
+}
+
 fn unpakistan(lua: MizLua, gid: GroupId) -> Result<()> {
     let ctx = unsafe { Context::get_mut() };
     let (side, slot) = slot_for_group(lua, ctx, &gid)?;
+    if let Some(msg) = too_close_to_logistics(ctx, lua, side, &slot)? {
+        ctx.db.msgs().panel_to_group(10, false, gid, msg);
+        return Ok(());
+    }
     match ctx.db.unpakistan(lua, &ctx.idx, &slot) {
-        Ok(unpakistan) => {
+        Ok((unpakistan, jtac)) => {
             let player = player_name(&ctx.db, &slot);
             let msg = format_compact!("{player} {unpakistan}");
             ctx.db.msgs().panel_to_side(10, false, side, msg);
+            if let Some(jtac_gid) = jtac {
+                if let Err(e) = establish_jtac(lua, ctx, side, jtac_gid) {
+                    ctx.db.msgs().panel_to_group(
+                        10,
+                        false,
+                        gid,
+                        format_compact!("unpacked, but the jtac failed to come up: {e}"),
+                    )
+                }
+            }
         }
         Err(e) => {
             let msg = format_compact!("{}", e);
@@ -98,6 +137,32 @@ fn unpakistan(lua: MizLua, gid: GroupId) -> Result<()> {
     Ok(())
 }
 
+/// laser code handed to a freshly unpacked JTAC when the mission doesn't
+/// set `cfg.default_jtac_code`; a 4 digit code in the band CTLD-style
+/// mods conventionally reserve for ground FACs, unlikely to collide with
+/// a mission-placed JTAC's code
+const DEFAULT_JTAC_CODE: u16 = 1688;
+
+/// finish standing a deployable JTAC up the moment `unpakistan` spawns
+/// it: join the group to the live `ctx.jtac` tracker with the mission's
+/// default laser code, flip auto laser on so it starts designating
+/// without the squad having to visit the menu first, build its `Status`/
+/// `Shift`/`Filter`/`Code` submenu, and let the side know forward
+/// designation is now available at this location
+fn establish_jtac(lua: MizLua, ctx: &mut Context, side: Side, gid: db::GroupId) -> Result<()> {
+    let code = ctx.db.cfg().default_jtac_code.unwrap_or(DEFAULT_JTAC_CODE);
+    ctx.jtac.register(lua, &ctx.db, &gid, code)?;
+    ctx.jtac.toggle_auto_laser(lua, &gid)?;
+    add_menu_for_jtac(lua, side, gid)?;
+    ctx.db.msgs().panel_to_side(
+        10,
+        false,
+        side,
+        format_compact!("{gid} is now operational as a JTAC, laser code {code}"),
+    );
+    Ok(())
+}
+
 fn load_crate(lua: MizLua, gid: GroupId) -> Result<()> {
     let ctx = unsafe { Context::get_mut() };
     let (side, slot) = slot_for_group(lua, ctx, &gid)?;
@@ -136,7 +201,8 @@ fn load_crate(lua: MizLua, gid: GroupId) -> Result<()> {
                 dep.limit,
                 enforce
             );
-            ctx.db.msgs().panel_to_group(10, false, gid, msg)
+            ctx.db.msgs().panel_to_group(10, false, gid, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => {
             let msg = format_compact!("crate could not be loaded: {}", e);
@@ -152,7 +218,8 @@ fn unload_crate(lua: MizLua, gid: GroupId) -> Result<()> {
     match ctx.db.unload_crate(lua, &ctx.idx, &slot) {
         Ok(cr) => {
             let msg = format_compact!("{} crate unloaded", cr.name);
-            ctx.db.msgs().panel_to_group(10, false, gid, msg)
+            ctx.db.msgs().panel_to_group(10, false, gid, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => {
             let msg = format_compact!("{}", e);
@@ -162,6 +229,32 @@ fn unload_crate(lua: MizLua, gid: GroupId) -> Result<()> {
     Ok(())
 }
 
+/// the combined weight (kg) of every crate and troop currently on the
+/// manifest; shared by [`list_cargo_for_slot`]'s display and
+/// [`refresh_cargo_mass`]'s push into the unit's actual internal cargo
+fn total_cargo_weight(cargo: &Cargo) -> f64 {
+    let mut total = 0.;
+    for (_, cr) in &cargo.crates {
+        total += cr.weight
+    }
+    for tr in &cargo.troops {
+        total += tr.weight
+    }
+    total
+}
+
+/// push the slot's current manifest weight into the live unit's internal
+/// cargo mass so a loaded Huey/Mi-8 actually flies heavier; called after
+/// every op that changes the manifest (load/unload crate, load/unload/
+/// extract/return troops, and `hover`'s preload-on-spawn)
+pub(super) fn refresh_cargo_mass(lua: MizLua, ctx: &mut Context, slot: &SlotId) -> Result<()> {
+    let empty = Cargo::default();
+    let cargo = ctx.db.list_cargo(slot).unwrap_or(&empty);
+    let mass = total_cargo_weight(cargo);
+    ctx.db.slot_instance_unit(lua, &ctx.idx, slot)?.set_cargo_mass(mass)?;
+    Ok(())
+}
+
 pub(super) fn list_cargo_for_slot(lua: MizLua, ctx: &mut Context, slot: &SlotId) -> Result<()> {
     let cargo = Cargo::default();
     let cargo = ctx.db.list_cargo(&slot).unwrap_or(&cargo);
@@ -184,14 +277,12 @@ pub(super) fn list_cargo_for_slot(lua: MizLua, ctx: &mut Context, slot: &SlotId)
         capacity.total_slots
     ));
     msg.push_str("----------------------------\n");
-    let mut total = 0;
     for (_, cr) in &cargo.crates {
         msg.push_str(&format_compact!(
             "{} crate weighing {} kg\n",
             cr.name,
             cr.weight
         ));
-        total += cr.weight
     }
     for tr in &cargo.troops {
         msg.push_str(&format_compact!(
@@ -199,9 +290,9 @@ pub(super) fn list_cargo_for_slot(lua: MizLua, ctx: &mut Context, slot: &SlotId)
             tr.name,
             tr.weight
         ));
-        total += tr.weight
     }
-    if total > 0 {
+    let total = total_cargo_weight(cargo);
+    if total > 0. {
         msg.push_str("----------------------------\n");
     }
     msg.push_str(&format_compact!("total cargo weight: {} kg", total as u32));
@@ -253,9 +344,82 @@ fn destroy_nearby_crate(lua: MizLua, gid: GroupId) -> Result<()> {
     Ok(())
 }
 
-fn spawn_crate(lua: MizLua, arg: ArgTuple<GroupId, String>) -> Result<()> {
+/// matches DCS's `trigger.smokeColor`; stored as a bare `u8` on the wire
+/// (the `ArgTuple` second field) rather than a Lua table, the same way
+/// `jtac_filter`/`jtac_set_code` pass their bitmask/code as raw integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SmokeColor {
+    Green,
+    Red,
+    White,
+    Orange,
+    Blue,
+}
+
+impl SmokeColor {
+    const ALL: [SmokeColor; 5] = [
+        SmokeColor::Green,
+        SmokeColor::Red,
+        SmokeColor::White,
+        SmokeColor::Orange,
+        SmokeColor::Blue,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SmokeColor::Green => "Green",
+            SmokeColor::Red => "Red",
+            SmokeColor::White => "White",
+            SmokeColor::Orange => "Orange",
+            SmokeColor::Blue => "Blue",
+        }
+    }
+}
+
+impl From<SmokeColor> for u8 {
+    fn from(c: SmokeColor) -> Self {
+        c as u8
+    }
+}
+
+impl TryFrom<u8> for SmokeColor {
+    type Error = anyhow::Error;
+
+    fn try_from(v: u8) -> Result<Self> {
+        SmokeColor::ALL
+            .into_iter()
+            .nth(v as usize)
+            .ok_or_else(|| anyhow!("invalid smoke color {v}"))
+    }
+}
+
+fn smoke_nearby_crate(lua: MizLua, arg: ArgTuple<GroupId, u8>) -> Result<()> {
     let ctx = unsafe { Context::get_mut() };
     let (_side, slot) = slot_for_group(lua, ctx, &arg.fst)?;
+    if ctx.db.cfg().disable_all_smoke {
+        return Ok(());
+    }
+    let color = SmokeColor::try_from(arg.snd)?;
+    match ctx.db.smoke_crate(lua, &ctx.idx, &slot, color.into()) {
+        Ok(cr) => {
+            let msg = format_compact!("{} smoke popped on {}", color.name(), cr.name);
+            ctx.db.msgs().panel_to_group(10, false, arg.fst, msg)
+        }
+        Err(e) => ctx
+            .db
+            .msgs()
+            .panel_to_group(10, false, arg.fst, format_compact!("{}", e)),
+    }
+    Ok(())
+}
+
+fn spawn_crate(lua: MizLua, arg: ArgTuple<GroupId, String>) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    let (side, slot) = slot_for_group(lua, ctx, &arg.fst)?;
+    if let Some(msg) = too_close_to_logistics(ctx, lua, side, &slot)? {
+        ctx.db.msgs().panel_to_group(10, false, arg.fst, msg);
+        return Ok(());
+    }
     match ctx.db.spawn_crate(lua, &ctx.idx, &slot, &arg.snd) {
         Err(e) => ctx
             .db
@@ -277,11 +441,16 @@ fn spawn_crate(lua: MizLua, arg: ArgTuple<GroupId, String>) -> Result<()> {
     Ok(())
 }
 
-fn load_troops(lua: MizLua, arg: ArgTuple<GroupId, String>) -> Result<()> {
+fn load_troops(lua: MizLua, arg: ArgTuple<GroupId, ArgTuple<String, u32>>) -> Result<()> {
     let ctx = unsafe { Context::get_mut() };
     let (side, slot) = slot_for_group(lua, ctx, &arg.fst)?;
-    match ctx.db.load_troops(lua, &ctx.idx, &slot, &arg.snd) {
-        Ok(tr) => {
+    let (squad, requested) = (&arg.snd.fst, arg.snd.snd);
+    // `Db::load_troops` clamps `requested` to the squad size and whatever
+    // cargo capacity remains, then hands back how many actually boarded;
+    // everything downstream (unload/extract/return, weight accounting)
+    // operates on that real count, not the requested one
+    match ctx.db.load_troops(lua, &ctx.idx, &slot, squad, requested) {
+        Ok((tr, taken)) => {
             let (n, oldest) = ctx.db.number_troops_deployed(side, &tr.name)?;
             let player = player_name(&ctx.db, &slot);
             let enforce = match tr.limit_enforce {
@@ -299,8 +468,14 @@ fn load_troops(lua: MizLua, arg: ArgTuple<GroupId, String>) -> Result<()> {
                     }
                 },
             };
-            let msg = format_compact!("{player} loaded {}, {n}/{}, {}", tr.name, tr.limit, enforce);
-            ctx.db.msgs().panel_to_side(10, false, side, msg)
+            let msg = format_compact!(
+                "{player} loaded {taken} of {}, {n}/{}, {}",
+                tr.name,
+                tr.limit,
+                enforce
+            );
+            ctx.db.msgs().panel_to_side(10, false, side, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => ctx
             .db
@@ -317,7 +492,8 @@ fn unload_troops(lua: MizLua, gid: GroupId) -> Result<()> {
         Ok(tr) => {
             let player = player_name(&ctx.db, &slot);
             let msg = format_compact!("{player} dropped {} troops into the field", tr.name);
-            ctx.db.msgs().panel_to_side(10, false, side, msg)
+            ctx.db.msgs().panel_to_side(10, false, side, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => ctx
             .db
@@ -334,7 +510,8 @@ fn extract_troops(lua: MizLua, gid: GroupId) -> Result<()> {
         Ok(tr) => {
             let player = player_name(&ctx.db, &slot);
             let msg = format_compact!("{player} extracted {} troops from the field", tr.name);
-            ctx.db.msgs().panel_to_side(10, false, side, msg)
+            ctx.db.msgs().panel_to_side(10, false, side, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => ctx
             .db
@@ -351,7 +528,8 @@ fn return_troops(lua: MizLua, gid: GroupId) -> Result<()> {
         Ok(tr) => {
             let player = player_name(&ctx.db, &slot);
             let msg = format_compact!("{player} returned {} troops", tr.name);
-            ctx.db.msgs().panel_to_side(10, false, side, msg)
+            ctx.db.msgs().panel_to_side(10, false, side, msg);
+            refresh_cargo_mass(lua, ctx, &slot)?;
         }
         Err(e) => ctx
             .db
@@ -475,16 +653,41 @@ fn add_troops_menu_for_group(
         )?;
         let root = mc.add_submenu_for_group(group, "Squads".into(), Some(root))?;
         for sq in squads {
-            mc.add_command_for_group(
+            let sq_root = mc.add_submenu_for_group(
                 group,
                 format_compact!("Load {} squad", sq.name).into(),
                 Some(root.clone()),
-                load_troops,
-                ArgTuple {
-                    fst: group,
-                    snd: sq.name.clone(),
-                },
             )?;
+            // offer 1, 2, 4, .. up to the squad size so a full 10-man squad
+            // doesn't need ten separate menu entries, plus "All" for the
+            // whole squad in one go
+            let mut counts = vec![];
+            let mut n = 1;
+            while n < sq.size {
+                counts.push(n);
+                n *= 2;
+            }
+            counts.push(sq.size);
+            for n in counts {
+                let title = if n == sq.size {
+                    format_compact!("Load All ({})", n)
+                } else {
+                    format_compact!("Load {}", n)
+                };
+                mc.add_command_for_group(
+                    group,
+                    title.into(),
+                    Some(sq_root.clone()),
+                    load_troops,
+                    ArgTuple {
+                        fst: group,
+                        snd: ArgTuple {
+                            fst: sq.name.clone(),
+                            snd: n,
+                        },
+                    },
+                )?;
+            }
         }
     }
     Ok(())
@@ -539,6 +742,22 @@ fn add_cargo_menu_for_group(
         destroy_nearby_crate,
         group,
     )?;
+    if !cfg.disable_all_smoke {
+        let smoke_root =
+            mc.add_submenu_for_group(group, "Smoke Nearby Crate".into(), Some(root.clone()))?;
+        for color in SmokeColor::ALL {
+            mc.add_command_for_group(
+                group,
+                color.name().into(),
+                Some(smoke_root.clone()),
+                smoke_nearby_crate,
+                ArgTuple {
+                    fst: group,
+                    snd: color.into(),
+                },
+            )?;
+        }
+    }
     let root = mc.add_submenu_for_group(group, "Crates".into(), Some(root.clone()))?;
     let rep = &cfg.repair_crate[side];
     mc.add_command_for_group(
@@ -686,6 +905,154 @@ fn jtac_set_code(lua: MizLua, arg: ArgTuple<db::GroupId, u16>) -> Result<()> {
     jtac_status(lua, arg.fst)
 }
 
+fn jtac_list_active(_lua: MizLua, side: Side) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    let active = ctx.jtac.active(side);
+    let mut msg = CompactString::new("Active JTACs\n----------------------------\n");
+    if active.is_empty() {
+        msg.push_str("none\n");
+    } else {
+        for gid in active {
+            msg.push_str(&format_compact!("{}\n", ctx.jtac.jtac_status(&ctx.db, gid)?));
+        }
+    }
+    ctx.db.msgs().panel_to_side(15, false, side, msg);
+    Ok(())
+}
+
+fn jtac_cycle_current(_lua: MizLua, side: Side) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    let msg = match ctx.jtac.cycle(side) {
+        Some(gid) => format_compact!("current jtac is now {gid}"),
+        None => format_compact!("no jtacs up for {:?}", side),
+    };
+    ctx.db.msgs().panel_to_side(10, false, side, msg);
+    Ok(())
+}
+
+/// the JTAC [`jtac::Jtacs::current`] is pointing at for `side`, i.e. the
+/// one the coalition-wide "Current" submenu commands below act on
+fn current_jtac(ctx: &Context, side: Side) -> Result<db::GroupId> {
+    ctx.jtac
+        .current(side)
+        .ok_or_else(|| anyhow!("no jtacs up for {:?}", side))
+}
+
+fn jtac_status_current(lua: MizLua, side: Side) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    match current_jtac(ctx, side) {
+        Ok(gid) => jtac_status(lua, gid),
+        Err(e) => {
+            ctx.db.msgs().panel_to_side(10, false, side, format_compact!("{e}"));
+            Ok(())
+        }
+    }
+}
+
+fn jtac_toggle_auto_laser_current(lua: MizLua, side: Side) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    match current_jtac(ctx, side) {
+        Ok(gid) => jtac_toggle_auto_laser(lua, gid),
+        Err(e) => {
+            ctx.db.msgs().panel_to_side(10, false, side, format_compact!("{e}"));
+            Ok(())
+        }
+    }
+}
+
+/// "request smoke/IR marking on the designated target" for whichever
+/// JTAC is current -- reuses the same toggle the per-group submenu
+/// exposes, since marking its priority contact is exactly what
+/// `toggle_smoke_target` already does
+fn jtac_mark_current(lua: MizLua, side: Side) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    match current_jtac(ctx, side) {
+        Ok(gid) => jtac_toggle_smoke_target(lua, gid),
+        Err(e) => {
+            ctx.db.msgs().panel_to_side(10, false, side, format_compact!("{e}"));
+            Ok(())
+        }
+    }
+}
+
+fn jtac_set_code_current(lua: MizLua, arg: ArgTuple<Side, u16>) -> Result<()> {
+    let ctx = unsafe { Context::get_mut() };
+    match current_jtac(ctx, arg.fst) {
+        Ok(gid) => jtac_set_code(lua, ArgTuple { fst: gid, snd: arg.snd }),
+        Err(e) => {
+            ctx.db
+                .msgs()
+                .panel_to_side(10, false, arg.fst, format_compact!("{e}"));
+            Ok(())
+        }
+    }
+}
+
+/// the coalition-wide `JTAC` root: an overview of every deployed JTAC
+/// plus a "Current" submenu (changed only by Cycle Current) that lets a
+/// player direct CAS without
+/// drilling into that JTAC's per-group menu first
+fn add_jtac_coalition_menu(mc: &MissionCommands, side: Side) -> Result<()> {
+    let root = mc.add_submenu_for_coalition(side, "JTAC".into(), None)?;
+    mc.add_command_for_coalition(
+        side,
+        "List Active".into(),
+        Some(root.clone()),
+        jtac_list_active,
+        side,
+    )?;
+    mc.add_command_for_coalition(
+        side,
+        "Cycle Current".into(),
+        Some(root.clone()),
+        jtac_cycle_current,
+        side,
+    )?;
+    let cur = mc.add_submenu_for_coalition(side, "Current".into(), Some(root))?;
+    mc.add_command_for_coalition(
+        side,
+        "Status".into(),
+        Some(cur.clone()),
+        jtac_status_current,
+        side,
+    )?;
+    mc.add_command_for_coalition(
+        side,
+        "Toggle Auto Laser".into(),
+        Some(cur.clone()),
+        jtac_toggle_auto_laser_current,
+        side,
+    )?;
+    mc.add_command_for_coalition(
+        side,
+        "Mark Target".into(),
+        Some(cur.clone()),
+        jtac_mark_current,
+        side,
+    )?;
+    let code_root = mc.add_submenu_for_coalition(side, "Code".into(), Some(cur))?;
+    let hundreds_root =
+        mc.add_submenu_for_coalition(side, "Hundreds".into(), Some(code_root.clone()))?;
+    let tens_root = mc.add_submenu_for_coalition(side, "Tens".into(), Some(code_root.clone()))?;
+    let ones_root = mc.add_submenu_for_coalition(side, "Ones".into(), Some(code_root.clone()))?;
+    for (scale, root) in [(100, &hundreds_root), (10, &tens_root), (1, &ones_root)] {
+        let range = if scale == 100 { 0..=6 } else { 0..=8 };
+        for n in range {
+            mc.add_command_for_coalition(
+                side,
+                format_compact!("{n}").into(),
+                Some(root.clone()),
+                jtac_set_code_current,
+                ArgTuple {
+                    fst: side,
+                    snd: n * scale,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
 pub fn add_menu_for_jtac(lua: MizLua, side: Side, group: db::GroupId) -> Result<()> {
     let mc = MissionCommands::singleton(lua)?;
     let root = mc.add_submenu_for_coalition(side, "JTAC".into(), None)?;
@@ -764,6 +1131,33 @@ pub fn add_menu_for_jtac(lua: MizLua, side: Side, group: db::GroupId) -> Result<
     Ok(())
 }
 
+/// a partial override of a [`CarryCap`]'s type-derived slot counts;
+/// `None` fields leave the `cfg.cargo` default alone, `Some` replaces it
+/// -- modeled on rust-analyzer's `CfgOverrides`, which layers a diff onto
+/// a computed default rather than replacing the whole table. looked up
+/// by group name first, then unit name, so a unit-level override (one
+/// specific airframe) wins over a group-level one (the whole flight)
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct CargoOverride {
+    troop_slots: Option<u8>,
+    crate_slots: Option<u8>,
+    total_slots: Option<u8>,
+}
+
+impl CargoOverride {
+    fn apply(self, troop_slots: &mut u8, crate_slots: &mut u8, total_slots: &mut u8) {
+        if let Some(v) = self.troop_slots {
+            *troop_slots = v;
+        }
+        if let Some(v) = self.crate_slots {
+            *crate_slots = v;
+        }
+        if let Some(v) = self.total_slots {
+            *total_slots = v;
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 struct CarryCap {
     troops: bool,
@@ -772,6 +1166,7 @@ struct CarryCap {
 
 impl CarryCap {
     fn new(cfg: &Cfg, group: &Group) -> Result<CarryCap> {
+        let group_name = group.name()?;
         Ok(group
             .units()?
             .into_iter()
@@ -779,11 +1174,20 @@ impl CarryCap {
                 let mut acc = acc?;
                 let unit = unit?;
                 let typ = unit.typ()?;
+                let unit_name = unit.name()?;
                 match cfg.cargo.get(&**typ) {
                     None => Ok(acc),
                     Some(c) => {
-                        acc.troops |= c.troop_slots > 0 && c.total_slots > 0;
-                        acc.crates |= c.crate_slots > 0 && c.total_slots > 0;
+                        let (mut troop_slots, mut crate_slots, mut total_slots) =
+                            (c.troop_slots, c.crate_slots, c.total_slots);
+                        if let Some(over) = cfg.cargo_overrides.get(&*group_name) {
+                            over.apply(&mut troop_slots, &mut crate_slots, &mut total_slots);
+                        }
+                        if let Some(over) = cfg.cargo_overrides.get(&*unit_name) {
+                            over.apply(&mut troop_slots, &mut crate_slots, &mut total_slots);
+                        }
+                        acc.troops |= troop_slots > 0 && total_slots > 0;
+                        acc.crates |= crate_slots > 0 && total_slots > 0;
                         Ok(acc)
                     }
                 }
@@ -791,6 +1195,33 @@ impl CarryCap {
     }
 }
 
+/// compute [`CarryCap`] for `group` and wire up whichever of the cargo/
+/// troops/EWR submenus it qualifies for; shared by `init`'s one-time walk
+/// of the mission file and by anything that needs the same setup for a
+/// group that starts existing later (a reinforcement spawn, or a respawn
+/// after a snapshot restore, which hands the group a fresh engine id)
+pub fn add_menus_for_group(cfg: &Cfg, mc: &MissionCommands, side: Side, group: &Group) -> Result<()> {
+    let cap = CarryCap::new(cfg, group)?;
+    let gid = group.id()?;
+    if cap.crates {
+        add_cargo_menu_for_group(cfg, mc, &side, gid)?
+    }
+    if cap.troops {
+        add_troops_menu_for_group(cfg, mc, &side, gid)?
+    }
+    add_ewr_menu_for_group(mc, gid)?;
+    Ok(())
+}
+
+/// tear down every menu entry registered for `group`; the counterpart to
+/// [`add_menus_for_group`], called once a group is gone for good (or is
+/// about to be replaced by a respawn under a new engine id) so the F10
+/// menu doesn't accumulate entries pointing at nothing
+pub fn remove_menus_for_group(lua: MizLua, group: GroupId) -> Result<()> {
+    MissionCommands::singleton(lua)?.remove_item_for_group(group, None)?;
+    Ok(())
+}
+
 pub(super) fn init(ctx: &Context, lua: MizLua) -> Result<()> {
     debug!("initializing menus");
     let cfg = ctx.db.cfg();
@@ -801,31 +1232,28 @@ pub(super) fn init(ctx: &Context, lua: MizLua) -> Result<()> {
         for country in coa.countries()? {
             let country = country?;
             for heli in country.helicopters()? {
-                let heli = heli?;
-                let cap = CarryCap::new(cfg, &heli)?;
-                let gid = heli.id()?;
-                if cap.crates {
-                    add_cargo_menu_for_group(cfg, &mc, &side, gid)?
-                }
-                if cap.troops {
-                    add_troops_menu_for_group(cfg, &mc, &side, gid)?
-                }
-                add_ewr_menu_for_group(&mc, gid)?;
+                add_menus_for_group(cfg, &mc, side, &heli?)?;
             }
             for plane in country.planes()? {
-                let plane = plane?;
-                let cap = CarryCap::new(cfg, &plane)?;
-                let gid = plane.id()?;
-                if cap.crates {
-                    add_cargo_menu_for_group(cfg, &mc, &side, gid)?
-                }
-                if cap.troops {
-                    add_troops_menu_for_group(cfg, &mc, &side, gid)?
-                }
-                add_ewr_menu_for_group(&mc, gid)?;
+                add_menus_for_group(cfg, &mc, side, &plane?)?;
             }
         }
-        let _ = mc.add_submenu_for_coalition(side, "JTAC".into(), None)?;
+        add_jtac_coalition_menu(&mc, side)?;
     }
+    // groups that spawn after mission start -- reinforcements, or a
+    // respawn that hands a group a fresh engine id after a snapshot
+    // restore -- don't pass through the walk above, so they'd otherwise
+    // never get their menus; and a despawned group's old entries would
+    // otherwise linger forever pointed at an id that no longer exists.
+    // hooking both directions into `Db`'s spawn/despawn path turns menu
+    // setup from a mission-load one-shot into something that tracks a
+    // group's actual lifecycle
+    let ctx = unsafe { Context::get_mut() };
+    ctx.db.on_group_spawned(|lua, side, group| {
+        let ctx = unsafe { Context::get_mut() };
+        add_menus_for_group(ctx.db.cfg(), &MissionCommands::singleton(lua)?, side, &group)
+    });
+    ctx.db
+        .on_group_despawned(|lua, gid| remove_menus_for_group(lua, gid));
     Ok(())
 }
@@ -0,0 +1,93 @@
+use crate::db::SpawnLoc;
+use anyhow::{bail, Context, Result};
+use dcso3::{coalition::Side, env::miz::GroupKind, String as DString, Vector2};
+use serde_derive::Deserialize;
+use std::{fs, path::Path};
+
+/// one entry of a declarative spawn plan: what to spawn, as which side,
+/// and where. mirrors the arguments to `Db::spawn_template_as_new` so a
+/// whole ORBAT can be described in a TOML file instead of imperative calls
+#[derive(Debug, Clone)]
+pub struct PlanEntry {
+    pub template_name: DString,
+    pub side: Side,
+    pub kind: GroupKind,
+    pub location: SpawnLoc,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLocation {
+    pos: Option<[f64; 2]>,
+    trigger: Option<std::string::String>,
+    offset: Option<[f64; 2]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEntry {
+    template_name: std::string::String,
+    side: std::string::String,
+    kind: std::string::String,
+    location: RawLocation,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawPlan {
+    #[serde(default)]
+    entry: Vec<RawEntry>,
+}
+
+fn parse_side(key: &str, s: &str) -> Result<Side> {
+    Ok(match s.to_lowercase().as_str() {
+        "blue" => Side::Blue,
+        "red" => Side::Red,
+        "neutral" | "neutrals" => Side::Neutral,
+        other => bail!("{key}: unrecognized side {other:?}"),
+    })
+}
+
+fn parse_kind(key: &str, s: &str) -> Result<GroupKind> {
+    Ok(match s.to_lowercase().as_str() {
+        "vehicle" => GroupKind::Vehicle,
+        "any" => GroupKind::Any,
+        other => bail!("{key}: unrecognized kind {other:?}"),
+    })
+}
+
+fn parse_location(key: &str, loc: RawLocation) -> Result<SpawnLoc> {
+    match (loc.pos, loc.trigger, loc.offset) {
+        (Some([x, y]), None, None) => Ok(SpawnLoc::AtPos(Vector2::new(x, y))),
+        (None, Some(name), offset) => {
+            let [dx, dy] = offset.unwrap_or([0., 0.]);
+            Ok(SpawnLoc::AtTrigger {
+                name: DString::from(name.as_str()),
+                offset: Vector2::new(dx, dy),
+            })
+        }
+        _ => bail!(
+            "{key}: location must be either {{ pos = [x, y] }} or \
+             {{ trigger = \"...\", offset = [dx, dy] }}"
+        ),
+    }
+}
+
+/// parse a TOML spawn plan from `path`. every field error names the
+/// offending entry and the bad value instead of surfacing a generic serde
+/// failure, so a mission author gets something actionable back
+pub fn load(path: &Path) -> Result<Vec<PlanEntry>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading spawn plan {:?}", path))?;
+    let raw: RawPlan =
+        toml::from_str(&text).with_context(|| format!("parsing spawn plan {:?}", path))?;
+    raw.entry
+        .into_iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let key = format!("entry[{i}] ({})", e.template_name);
+            Ok(PlanEntry {
+                side: parse_side(&key, &e.side)?,
+                kind: parse_kind(&key, &e.kind)?,
+                location: parse_location(&key, e.location)?,
+                template_name: DString::from(e.template_name.as_str()),
+            })
+        })
+        .collect()
+}
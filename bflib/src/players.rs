@@ -0,0 +1,114 @@
+use compact_str::format_compact;
+use dcso3::{coalition::Side, String};
+use fxhash::FxHashMap;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// namespace used to derive a stable player id from a DCS `ucid`; fixed so
+/// the same ucid always hashes to the same `PlayerId` across restarts
+const UCID_NAMESPACE: Uuid = Uuid::from_bytes([
+    0x4d, 0x65, 0x72, 0x63, 0x73, 0x2d, 0x55, 0x43, 0x49, 0x44, 0x2d, 0x4e, 0x53, 0x50, 0x43, 0x45,
+]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PlayerId(Uuid);
+
+impl PlayerId {
+    pub fn from_ucid(ucid: &str) -> Self {
+        Self(Uuid::new_v5(&UCID_NAMESPACE, ucid.as_bytes()))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub ucid: String,
+    pub name: String,
+    pub side: Option<Side>,
+    pub last_slot: String,
+    pub points: i64,
+    pub banned: bool,
+}
+
+/// durable store of player identity keyed by a `PlayerId` derived from the
+/// DCS `ucid`, persisted alongside the `Db` so side locks, bans, and score
+/// survive a server restart
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Players {
+    by_id: FxHashMap<PlayerId, PlayerRecord>,
+}
+
+impl Players {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut tmp = PathBuf::from(path);
+        tmp.set_extension("tmp");
+        serde_json::to_writer(File::create(&tmp)?, self)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// called from `onPlayerTryConnect`; rejects banned ucids outright and
+    /// otherwise makes sure a record exists for future lookups
+    pub fn try_connect(&mut self, ucid: &str, name: &str) -> Result<PlayerId, String> {
+        let id = PlayerId::from_ucid(ucid);
+        let rec = self.by_id.entry(id).or_insert_with(|| PlayerRecord {
+            ucid: String::from(ucid),
+            ..PlayerRecord::default()
+        });
+        rec.name = String::from(name);
+        if rec.banned {
+            return Err(String::from("you are banned from this server"));
+        }
+        Ok(id)
+    }
+
+    /// enforce side lock; a player who has already picked a side for this
+    /// round can't jump to another one mid round
+    pub fn try_change_slot(&mut self, id: PlayerId, side: Side, slot: &str) -> Result<(), String> {
+        let rec = self.by_id.entry(id).or_default();
+        match rec.side {
+            None => rec.side = Some(side),
+            Some(locked) if locked == side => (),
+            Some(locked) => {
+                return Err(String::from(format_compact!(
+                    "you are locked to {:?} for this round",
+                    locked
+                )))
+            }
+        }
+        rec.last_slot = String::from(slot);
+        Ok(())
+    }
+
+    pub fn get(&self, id: PlayerId) -> Option<&PlayerRecord> {
+        self.by_id.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: PlayerId) -> Option<&mut PlayerRecord> {
+        self.by_id.get_mut(&id)
+    }
+
+    pub fn ban(&mut self, id: PlayerId) {
+        self.by_id.entry(id).or_default().banned = true;
+    }
+
+    pub fn unban(&mut self, id: PlayerId) {
+        if let Some(rec) = self.by_id.get_mut(&id) {
+            rec.banned = false;
+        }
+    }
+
+    pub fn add_points(&mut self, id: PlayerId, points: i64) {
+        self.by_id.entry(id).or_default().points += points;
+    }
+}
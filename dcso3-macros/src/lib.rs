@@ -0,0 +1,120 @@
+//! `#[derive(LuaTable)]`: generates `FromLua`/`IntoLua` for a plain
+//! struct of fields, reading/writing each one with `raw_get`/`raw_set`
+//! under its Lua key. Replaces the hand-written boilerplate every
+//! DCS record type in `coord`/`world`/`object` used to repeat by hand.
+//!
+//! ```ignore
+//! #[derive(LuaTable)]
+//! #[lua(class = "Group")]
+//! struct MGRSPos {
+//!     #[lua(rename = "UTMZone")]
+//!     utm_zone: String,
+//!     #[lua(rename = "MGRSDigraph")]
+//!     mgrs_digraph: String,
+//!     #[lua(rename = "Easting")]
+//!     easting: f64,
+//!     #[lua(rename = "Northing")]
+//!     northing: f64,
+//! }
+//! ```
+//!
+//! Each field's Lua key defaults to its Rust name; `#[lua(rename = "...")]`
+//! overrides it for the fields (like MGRS's `UTMZone`/`MGRSDigraph`/
+//! `Easting`/`Northing`) whose
+//! DCS key doesn't match Rust naming conventions. A struct-level
+//! `#[lua(class = "...")]` threads that name through to `as_tbl` as the
+//! class guard, so the generated `FromLua` rejects a table that doesn't
+//! implement it, the same check hand-written wrappers already pass to
+//! `as_tbl` as their second argument.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+#[proc_macro_derive(LuaTable, attributes(lua))]
+pub fn derive_lua_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let class = struct_class(&input);
+    let class_arg = match &class {
+        Some(c) => quote! { Some(#c) },
+        None => quote! { None },
+    };
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(f) => &f.named,
+            _ => panic!("#[derive(LuaTable)] only supports structs with named fields"),
+        },
+        _ => panic!("#[derive(LuaTable)] only supports structs"),
+    };
+
+    let mut from_fields = vec![];
+    let mut into_fields = vec![];
+    for f in fields {
+        let ident = f.ident.as_ref().unwrap();
+        let key = field_key(f);
+        from_fields.push(quote! { #ident: tbl.raw_get(#key)? });
+        into_fields.push(quote! { tbl.raw_set(#key, self.#ident)?; });
+    }
+
+    let name_str = LitStr::new(&name.to_string(), name.span());
+    let expanded = quote! {
+        impl<'lua> ::mlua::FromLua<'lua> for #name {
+            fn from_lua(value: ::mlua::Value<'lua>, _lua: &'lua ::mlua::Lua) -> ::mlua::Result<Self> {
+                let tbl = crate::as_tbl(#name_str, #class_arg, value)?;
+                Ok(Self { #(#from_fields),* })
+            }
+        }
+
+        impl<'lua> ::mlua::IntoLua<'lua> for #name {
+            fn into_lua(self, lua: &'lua ::mlua::Lua) -> ::mlua::Result<::mlua::Value<'lua>> {
+                let tbl = lua.create_table()?;
+                #(#into_fields)*
+                Ok(::mlua::Value::Table(tbl))
+            }
+        }
+    };
+    expanded.into()
+}
+
+fn struct_class(input: &DeriveInput) -> Option<LitStr> {
+    for attr in &input.attrs {
+        if !attr.path().is_ident("lua") {
+            continue;
+        }
+        let mut class = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("class") {
+                class = Some(meta.value()?.parse::<LitStr>()?);
+            }
+            Ok(())
+        })
+        .expect("invalid #[lua(..)] attribute");
+        if class.is_some() {
+            return class;
+        }
+    }
+    None
+}
+
+fn field_key(f: &syn::Field) -> LitStr {
+    for attr in &f.attrs {
+        if !attr.path().is_ident("lua") {
+            continue;
+        }
+        let mut rename = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?);
+            }
+            Ok(())
+        })
+        .expect("invalid #[lua(..)] attribute");
+        if let Some(rename) = rename {
+            return rename;
+        }
+    }
+    LitStr::new(&f.ident.as_ref().unwrap().to_string(), f.ident.span())
+}
@@ -67,16 +67,87 @@ impl Side {
         }
     }
 
+    /// kept for backward compatibility; callers that need to reason about
+    /// more than one enemy (e.g. the Merc1/Merc2/Merc3 factions) should use
+    /// [`Relations::enemies_of`] instead
     pub fn opposite(&self) -> Side {
-        match self {
-            Self::Blue => Self::Red,
-            Self::Red => Self::Blue,
-            Self::Neutral => Self::Neutral,
-            Self::Green => Self::Neutral,
-            Self::Merc1 => Self::Green,
-            Self::Merc2 => Self::Green,
-            Self::Merc3 => Self::Green
+        Relations::default()
+            .enemies_of(*self)
+            .next()
+            .unwrap_or(Side::Neutral)
+    }
+
+    fn idx(&self) -> usize {
+        *self as u8 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stance {
+    Hostile,
+    Neutral,
+    Allied,
+}
+
+/// who is hostile, neutral, or allied to whom. this replaces the old
+/// single-enemy `Side::opposite` with a full matrix over [`SIDES`], which
+/// is what the mercenary factions (Merc1/Merc2/Merc3) need: each can be at
+/// war with the common `Green` AI faction, with the other mercs, or both.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Relations([[Stance; SIDES.len()]; SIDES.len()]);
+
+impl Default for Relations {
+    /// reproduces the relationships the old `opposite()` function expressed:
+    /// Blue and Red are mutually hostile, and each Merc faction is hostile
+    /// to the Green AI faction. everything else defaults to neutral, and a
+    /// side is always allied with itself.
+    fn default() -> Self {
+        let mut m = [[Stance::Neutral; SIDES.len()]; SIDES.len()];
+        for side in SIDES {
+            m[side.idx()][side.idx()] = Stance::Allied;
+        }
+        let mut hostile = |a: Side, b: Side| {
+            m[a.idx()][b.idx()] = Stance::Hostile;
+            m[b.idx()][a.idx()] = Stance::Hostile;
+        };
+        hostile(Side::Blue, Side::Red);
+        hostile(Side::Merc1, Side::Green);
+        hostile(Side::Merc2, Side::Green);
+        hostile(Side::Merc3, Side::Green);
+        Self(m)
+    }
+}
+
+impl Relations {
+    pub fn stance(&self, a: Side, b: Side) -> Stance {
+        self.0[a.idx()][b.idx()]
+    }
+
+    pub fn enemies_of(&self, side: Side) -> impl Iterator<Item = Side> + '_ {
+        SIDES
+            .into_iter()
+            .filter(move |other| self.stance(side, *other) == Stance::Hostile)
+    }
+
+    /// a mission's relationship matrix must be symmetric; asymmetric
+    /// entries would mean A considers itself at war with B while B thinks
+    /// it's at peace with A, which no part of the targeting/spawn code
+    /// could sensibly act on
+    pub fn validate(&self) -> Result<()> {
+        for a in SIDES {
+            for b in SIDES {
+                if self.stance(a, b) != self.stance(b, a) {
+                    bail!(
+                        "relations matrix is not symmetric for {:?}/{:?}: {:?} != {:?}",
+                        a,
+                        b,
+                        self.stance(a, b),
+                        self.stance(b, a)
+                    )
+                }
+            }
         }
+        Ok(())
     }
 }
 
@@ -1,5 +1,6 @@
 use super::{as_tbl, String};
 use crate::{wrapped_table, LuaVec3};
+use dcso3_macros::LuaTable;
 use mlua::{prelude::*, Value};
 use serde_derive::{Deserialize, Serialize};
 use std::ops::Deref;
@@ -11,37 +12,22 @@ pub struct LLPos {
     pub altitude: f64,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// the `raw_get`/`raw_set` boilerplate this used to hand-write is exactly
+/// what `#[derive(LuaTable)]` exists to collapse; `rename` carries the
+/// DCS `UTMZone`/`MGRSDigraph` keys, which don't match the Rust field
+/// names
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, LuaTable)]
 pub struct MGRSPos {
+    #[lua(rename = "UTMZone")]
     utm_zone: String,
+    #[lua(rename = "MGRSDigraph")]
     mgrs_digraph: String,
+    #[lua(rename = "Easting")]
     easting: f64,
+    #[lua(rename = "Northing")]
     northing: f64,
 }
 
-impl<'lua> FromLua<'lua> for MGRSPos {
-    fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
-        let tbl = as_tbl("MGRSPos", None, value)?;
-        Ok(MGRSPos {
-            utm_zone: tbl.raw_get("UTMZone")?,
-            mgrs_digraph: tbl.raw_get("MGRSDigraph")?,
-            easting: tbl.raw_get("Easting")?,
-            northing: tbl.raw_get("Northing")?,
-        })
-    }
-}
-
-impl<'lua> IntoLua<'lua> for MGRSPos {
-    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
-        let tbl = lua.create_table()?;
-        tbl.raw_set("UTMZone", self.utm_zone)?;
-        tbl.raw_set("MGRSDigraph", self.mgrs_digraph)?;
-        tbl.raw_set("Easting", self.easting)?;
-        tbl.raw_set("Northing", self.northing)?;
-        Ok(Value::Table(tbl))
-    }
-}
-
 wrapped_table!(Coord, None);
 
 impl<'lua> Coord<'lua> {
@@ -0,0 +1,136 @@
+use super::{as_tbl, cvt_err, unit::Unit};
+use enumflags2::bitflags;
+use mlua::{prelude::*, Value};
+use serde_derive::Serialize;
+
+/// the discriminants of [`Event`], used as a filter bitmask by
+/// [`crate::world::EventBus::subscribe`] so a subscriber only pays for the
+/// event kinds it actually asked for
+#[bitflags]
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EventKind {
+    Shot,
+    Hit,
+    Takeoff,
+    Land,
+    Crash,
+    Ejection,
+    Birth,
+    Dead,
+    PlayerEnterUnit,
+    MissionEnd,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Shot<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Takeoff<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Land<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Crash<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ejection<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Birth<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Dead<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerEnterUnit<'lua> {
+    pub initiator: Unit<'lua>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MissionEnd {}
+
+/// a DCS world event, translated from the Lua table DCS hands to
+/// `addEventHandler`'s `onEvent` callback. the numeric `id` field DCS uses
+/// to discriminate events mirrors `world.event.S_EVENT_*`
+#[derive(Debug, Clone, Serialize)]
+pub enum Event<'lua> {
+    Shot(Shot<'lua>),
+    Hit(Hit<'lua>),
+    Takeoff(Takeoff<'lua>),
+    Land(Land<'lua>),
+    Crash(Crash<'lua>),
+    Ejection(Ejection<'lua>),
+    Birth(Birth<'lua>),
+    Dead(Dead<'lua>),
+    PlayerEnterUnit(PlayerEnterUnit<'lua>),
+    MissionEnd(MissionEnd),
+}
+
+impl<'lua> Event<'lua> {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Shot(_) => EventKind::Shot,
+            Self::Hit(_) => EventKind::Hit,
+            Self::Takeoff(_) => EventKind::Takeoff,
+            Self::Land(_) => EventKind::Land,
+            Self::Crash(_) => EventKind::Crash,
+            Self::Ejection(_) => EventKind::Ejection,
+            Self::Birth(_) => EventKind::Birth,
+            Self::Dead(_) => EventKind::Dead,
+            Self::PlayerEnterUnit(_) => EventKind::PlayerEnterUnit,
+            Self::MissionEnd(_) => EventKind::MissionEnd,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for Event<'lua> {
+    fn from_lua(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let tbl = as_tbl("Event", None, value)?;
+        let id: u8 = tbl.raw_get("id")?;
+        macro_rules! initiator {
+            () => {
+                tbl.raw_get::<_, Unit>("initiator")?
+            };
+        }
+        Ok(match id {
+            1 => Self::Shot(Shot { initiator: initiator!() }),
+            2 => Self::Hit(Hit { initiator: initiator!() }),
+            3 => Self::Takeoff(Takeoff { initiator: initiator!() }),
+            4 => Self::Land(Land { initiator: initiator!() }),
+            5 => Self::Crash(Crash { initiator: initiator!() }),
+            6 => Self::Ejection(Ejection { initiator: initiator!() }),
+            15 => Self::Birth(Birth { initiator: initiator!() }),
+            8 => Self::Dead(Dead { initiator: initiator!() }),
+            21 => Self::PlayerEnterUnit(PlayerEnterUnit { initiator: initiator!() }),
+            24 => Self::MissionEnd(MissionEnd {}),
+            _ => return Err(cvt_err("Event")),
+        })
+    }
+}
+
+impl<'lua> IntoLua<'lua> for Event<'lua> {
+    fn into_lua(self, _lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        Err(cvt_err("Event"))
+    }
+}
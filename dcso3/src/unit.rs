@@ -0,0 +1,31 @@
+use crate::wrapped_table;
+use mlua::prelude::*;
+
+wrapped_table!(Unit, None);
+
+impl<'lua> Unit<'lua> {
+    /// the airframe's maximum internal cargo mass (kg), read from the
+    /// unit's static description; [`Self::set_cargo_mass`] clamps to this
+    /// so an overloaded manifest can't exceed what the airframe can
+    /// actually lift
+    pub fn max_cargo_mass(&self) -> LuaResult<f64> {
+        let desc: LuaTable = self.t.call_method("getDesc", ())?;
+        desc.raw_get("Kmax")
+    }
+
+    /// the cargo mass (kg) currently applied to this unit by
+    /// [`Self::set_cargo_mass`]; read-only so other modules (and the F10
+    /// panel) can report remaining lift without duplicating the manifest
+    pub fn cargo_mass(&self) -> LuaResult<f64> {
+        self.t.call_method("getInternalCargo", ())
+    }
+
+    /// push `mass` kg of cargo weight into the unit's internal cargo mass
+    /// so a loaded Huey/Mi-8 actually flies heavier; clamped to
+    /// [`Self::max_cargo_mass`], and `0.` restores baseline performance
+    /// when the slot is vacated or the manifest is emptied
+    pub fn set_cargo_mass(&self, mass: f64) -> LuaResult<()> {
+        let max = self.max_cargo_mass().unwrap_or(mass);
+        self.t.call_method("setInternalCargo", mass.max(0.).min(max))
+    }
+}
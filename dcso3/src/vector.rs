@@ -0,0 +1,307 @@
+use super::as_tbl;
+use mlua::{prelude::*, MetaMethod, UserData, UserDataFields, UserDataMethods, Value};
+use serde_derive::{Deserialize, Serialize};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// DCS's 2d vector, `{x, y}`; used for map-plane positions (trigger
+/// zones, `Vec3::to_2d`, ...) where altitude doesn't matter
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, rhs: Self) -> f64 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// the zero vector normalizes to itself instead of producing NaNs;
+    /// every caller that cares about direction already has to special
+    /// case a zero-length input, so this just avoids handing it a trap
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len == 0. {
+            self
+        } else {
+            self / len
+        }
+    }
+
+    pub fn distance(self, rhs: Self) -> f64 {
+        (self - rhs).length()
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Div<f64> for Vec2 {
+    type Output = Self;
+    fn div(self, rhs: f64) -> Self {
+        Self::new(self.x / rhs, self.y / rhs)
+    }
+}
+
+/// registers the `x`/`y` fields and `+ - - * / :length() :normalize()
+/// :dot() :distance()` operators, mirroring [`Vec3`]'s `UserData` impl
+/// minus `cross` (undefined in 2d); `IntoLua` for [`Vec2`] comes from
+/// mlua's blanket `UserData -> IntoLua` impl, so a `Vec2` handed back to
+/// Lua is this same rich object rather than a plain table, while still
+/// reading and writing the DCS `x,y` shape field-for-field
+impl UserData for Vec2 {
+    fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_set("x", |_, this, v| {
+            this.x = v;
+            Ok(())
+        });
+        fields.add_field_method_set("y", |_, this, v| {
+            this.y = v;
+            Ok(())
+        });
+    }
+
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("length", |_, this, ()| Ok(this.length()));
+        methods.add_method("normalize", |_, this, ()| Ok(this.normalize()));
+        methods.add_method("dot", |_, this, rhs: Vec2| Ok(this.dot(rhs)));
+        methods.add_method("distance", |_, this, rhs: Vec2| Ok(this.distance(rhs)));
+        methods.add_meta_method(MetaMethod::Add, |_, this, rhs: Vec2| Ok(*this + rhs));
+        methods.add_meta_method(MetaMethod::Sub, |_, this, rhs: Vec2| Ok(*this - rhs));
+        methods.add_meta_method(MetaMethod::Unm, |_, this, ()| Ok(-*this));
+        methods.add_meta_method(MetaMethod::Mul, |_, this, rhs: f64| Ok(*this * rhs));
+        methods.add_meta_method(MetaMethod::Div, |_, this, rhs: f64| Ok(*this / rhs));
+    }
+}
+
+impl<'lua> FromLua<'lua> for Vec2 {
+    fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
+        // a `Vec2` we handed back to Lua round-trips as the `UserData`
+        // `IntoLua` produces, not the `{x, y}` table DCS itself sends, so
+        // both have to be accepted here
+        if let Value::UserData(ud) = &value {
+            if let Ok(v) = ud.borrow::<Self>() {
+                return Ok(*v);
+            }
+        }
+        let tbl = as_tbl("Vec2", None, value)?;
+        Ok(Self {
+            x: tbl.raw_get("x")?,
+            y: tbl.raw_get("y")?,
+        })
+    }
+}
+
+/// DCS's 3d vector, `{x, y, z}`; `y` is altitude, `x`/`z` are the map
+/// plane, matching the engine's left-handed, y-up convention throughout
+/// `Export`/`world`/`Object:getPosition`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, rhs: Self) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Self) -> Self {
+        Self::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len == 0. {
+            self
+        } else {
+            self / len
+        }
+    }
+
+    pub fn distance(self, rhs: Self) -> f64 {
+        (self - rhs).length()
+    }
+
+    /// drop altitude to project onto the map plane, the same reduction
+    /// `LLtoLO`'s callers already do by hand against raw `x`/`z`
+    pub fn to_2d(self) -> Vec2 {
+        Vec2::new(self.x, self.z)
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul<f64> for Vec3 {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Div<f64> for Vec3 {
+    type Output = Self;
+    fn div(self, rhs: f64) -> Self {
+        Self::new(self.x / rhs, self.y / rhs, self.z / rhs)
+    }
+}
+
+impl<'lua> FromLua<'lua> for Vec3 {
+    fn from_lua(value: Value<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
+        // same story as `Vec2::from_lua`: a `Vec3` we handed back to Lua
+        // comes back as `UserData`, not the `{x, y, z}` table DCS sends
+        if let Value::UserData(ud) = &value {
+            if let Ok(v) = ud.borrow::<Self>() {
+                return Ok(*v);
+            }
+        }
+        let tbl = as_tbl("Vec3", None, value)?;
+        Ok(Self {
+            x: tbl.raw_get("x")?,
+            y: tbl.raw_get("y")?,
+            z: tbl.raw_get("z")?,
+        })
+    }
+}
+
+/// registers the `x`/`y`/`z` fields and `+ - - * / :length() :normalize()
+/// :dot() :cross() :distance()` operators mission scripts get for free on
+/// Luau's built-in `vector` type; `IntoLua` for [`Vec3`] comes from
+/// mlua's blanket `UserData -> IntoLua` impl, so a `Vec3` handed back to
+/// Lua is this same rich object rather than a plain table, while still
+/// reading and writing the DCS `x,y,z` shape field-for-field
+impl UserData for Vec3 {
+    fn add_fields<'lua, F: UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.x));
+        fields.add_field_method_get("y", |_, this| Ok(this.y));
+        fields.add_field_method_get("z", |_, this| Ok(this.z));
+        fields.add_field_method_set("x", |_, this, v| {
+            this.x = v;
+            Ok(())
+        });
+        fields.add_field_method_set("y", |_, this, v| {
+            this.y = v;
+            Ok(())
+        });
+        fields.add_field_method_set("z", |_, this, v| {
+            this.z = v;
+            Ok(())
+        });
+    }
+
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("length", |_, this, ()| Ok(this.length()));
+        methods.add_method("normalize", |_, this, ()| Ok(this.normalize()));
+        methods.add_method("dot", |_, this, rhs: Vec3| Ok(this.dot(rhs)));
+        methods.add_method("cross", |_, this, rhs: Vec3| Ok(this.cross(rhs)));
+        methods.add_method("distance", |_, this, rhs: Vec3| Ok(this.distance(rhs)));
+        methods.add_meta_method(MetaMethod::Add, |_, this, rhs: Vec3| Ok(*this + rhs));
+        methods.add_meta_method(MetaMethod::Sub, |_, this, rhs: Vec3| Ok(*this - rhs));
+        methods.add_meta_method(MetaMethod::Unm, |_, this, ()| Ok(-*this));
+        methods.add_meta_method(MetaMethod::Mul, |_, this, rhs: f64| Ok(*this * rhs));
+        methods.add_meta_method(MetaMethod::Div, |_, this, rhs: f64| Ok(*this / rhs));
+    }
+}
+
+/// DCS's `Position3`, the `{p, x, y, z}` table `Object:getPosition`
+/// returns: `p` is the point in space, `x`/`y`/`z` are the object's
+/// forward/up/right unit vectors. plain data, not a vector-algebra type
+/// itself, so unlike [`Vec2`]/[`Vec3`] it stays a table on the wire
+/// rather than a `UserData`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Position3 {
+    pub p: Vec3,
+    pub x: Vec3,
+    pub y: Vec3,
+    pub z: Vec3,
+}
+
+impl<'lua> FromLua<'lua> for Position3 {
+    fn from_lua(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let tbl = as_tbl("Position3", None, value)?;
+        Ok(Self {
+            p: Vec3::from_lua(tbl.raw_get("p")?, lua)?,
+            x: Vec3::from_lua(tbl.raw_get("x")?, lua)?,
+            y: Vec3::from_lua(tbl.raw_get("y")?, lua)?,
+            z: Vec3::from_lua(tbl.raw_get("z")?, lua)?,
+        })
+    }
+}
+
+impl<'lua> IntoLua<'lua> for Position3 {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let tbl = lua.create_table()?;
+        tbl.raw_set("p", self.p)?;
+        tbl.raw_set("x", self.x)?;
+        tbl.raw_set("y", self.y)?;
+        tbl.raw_set("z", self.z)?;
+        Ok(Value::Table(tbl))
+    }
+}
@@ -1,14 +1,22 @@
-use super::{as_tbl, event::Event, unit::Unit, String};
-use crate::{airbase::Airbase, wrapped_table, Sequence};
-use compact_str::format_compact;
+use super::{
+    event::{Event, EventKind},
+    unit::Unit,
+};
+use crate::{airbase::Airbase, timer::Timer, wrapped_table, Sequence};
+use enumflags2::BitFlags;
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, StreamExt};
 use mlua::{prelude::*, Value};
 use serde_derive::Serialize;
 use std::{
+    collections::VecDeque,
+    future::Future,
     ops::Deref,
+    pin::Pin,
     sync::atomic::{AtomicU32, Ordering},
+    task::{Context as TaskCx, Poll, RawWaker, RawWakerVTable, Waker},
 };
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct HandlerId(u32);
 
 impl HandlerId {
@@ -16,51 +24,365 @@ impl HandlerId {
         static NEXT: AtomicU32 = AtomicU32::new(0);
         Self(NEXT.fetch_add(1, Ordering::Relaxed))
     }
+}
+
+/// returned by an [`EventBus`] subscriber to decide whether later
+/// subscribers still see the event it was just handed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+struct Subscriber {
+    id: HandlerId,
+    filter: BitFlags<EventKind>,
+    f: Box<dyn FnMut(&Lua, &Event) -> LuaResult<Control>>,
+}
+
+/// an async subscriber produces a future per matching event instead of
+/// handling it inline; the future is driven to completion across later
+/// [`World::poll_handlers`] calls rather than blocking `onEvent`
+struct AsyncSubscriber {
+    id: HandlerId,
+    filter: BitFlags<EventKind>,
+    f: Box<dyn Fn(&Lua, Event) -> LocalBoxFuture<'static, LuaResult<()>>>,
+}
+
+/// a future spawned by an [`AsyncSubscriber`], tagged with the id it came
+/// from so [`EventBus::unsubscribe`] can cancel in-flight work along with
+/// future dispatches
+struct Pending {
+    id: HandlerId,
+    fut: LocalBoxFuture<'static, LuaResult<()>>,
+}
+
+impl Future for Pending {
+    type Output = LuaResult<()>;
 
-    fn key(&self) -> String {
-        String(format_compact!("rustHandler{}", self.0))
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskCx) -> Poll<Self::Output> {
+        self.get_mut().fut.as_mut().poll(cx)
     }
 }
 
+// DCS's scripting api is single threaded, and `dispatch` only ever runs
+// from inside the one `onEvent` callback DCS calls back into on that same
+// thread, so a bare static is sound here as long as nothing re-enters it
+// concurrently; see bflib's `Context::get_mut` for the same argument
+static mut SUBSCRIBERS: Vec<Subscriber> = Vec::new();
+static mut ASYNC_SUBSCRIBERS: Vec<AsyncSubscriber> = Vec::new();
+// futures fired by `ASYNC_SUBSCRIBERS` but not yet finished; drained by
+// `World::poll_handlers`, which DCS calls once per sim frame
+static mut PENDING: Option<FuturesUnordered<Pending>> = None;
+
+fn pending() -> &'static mut FuturesUnordered<Pending> {
+    unsafe { PENDING.get_or_insert_with(FuturesUnordered::new) }
+}
+
+// a waker that does nothing: `FuturesUnordered` still polls a pending future
+// again only after that future wakes the per-task waker it wraps around
+// this one; a future that returns `Pending` without waking is not polled again
+const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |_| RawWaker::new(std::ptr::null(), &NOOP_VTABLE),
+    |_| (),
+    |_| (),
+    |_| (),
+);
+
+fn noop_waker() -> Waker {
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &NOOP_VTABLE)) }
+}
+
+/// an [`Event`], timestamped with the DCS model time it was dispatched at
+/// and serialized to JSON rather than kept as a typed `Event<'lua>`: the
+/// latter borrows from the `Lua` that fired it and can't outlive that one
+/// `onEvent` call, while JSON can sit in a ring buffer, get written to
+/// disk, or be inspected long after the frame that produced it
+pub type RecordedEvent = (f64, serde_json::Value);
+
+struct Recording {
+    capacity: usize,
+    buf: VecDeque<RecordedEvent>,
+}
+
+// recording is opt-in and mission-wide, same rationale as `SUBSCRIBERS`:
+// `dispatch` only ever runs on DCS's single scripting thread
+static mut RECORDING: Option<Recording> = None;
+
+/// a handle returned by [`World::start_recording`]; holding it keeps
+/// `dispatch` teeing events into the ring buffer, and dropping it stops
+/// recording. Whatever is still buffered is discarded on drop — call
+/// [`drain`](Self::drain) first if the caller wants it.
+pub struct Recorder(());
+
+impl Recorder {
+    /// remove and return everything captured so far, oldest first
+    pub fn drain(&self) -> Vec<RecordedEvent> {
+        unsafe {
+            RECORDING
+                .as_mut()
+                .map(|r| r.buf.drain(..).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        unsafe { RECORDING = None };
+    }
+}
+
+fn remove(id: HandlerId) {
+    unsafe {
+        SUBSCRIBERS.retain(|sub| sub.id != id);
+        ASYNC_SUBSCRIBERS.retain(|sub| sub.id != id);
+    }
+    pending().retain(|p| p.id != id);
+}
+
+fn dispatch(lua: &Lua, ev: Event) -> LuaResult<()> {
+    let kind = ev.kind();
+    unsafe {
+        if let Some(rec) = RECORDING.as_mut() {
+            // a recording is diagnostic, so a handler that doesn't
+            // serialize (or a clock read that fails) shouldn't stop real
+            // fan-out below; it just skips that one entry
+            if let Ok(payload) = serde_json::to_value(&ev) {
+                if let Ok(now) = Timer::singleton(lua).and_then(|t| t.get_time()) {
+                    if rec.buf.len() >= rec.capacity {
+                        rec.buf.pop_front();
+                    }
+                    rec.buf.push_back((now, payload));
+                }
+            }
+        }
+        for sub in SUBSCRIBERS.iter_mut() {
+            if sub.filter.contains(kind) {
+                match (sub.f)(lua, &ev)? {
+                    Control::Continue => (),
+                    Control::Stop => break,
+                }
+            }
+        }
+        for sub in ASYNC_SUBSCRIBERS.iter() {
+            if sub.filter.contains(kind) {
+                pending().push(Pending {
+                    id: sub.id,
+                    fut: (sub.f)(lua, ev.clone()),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 wrapped_table!(World, None);
 
+/// a single DCS `addEventHandler` registration that fans events out to many
+/// in-process subscribers, so N subscribers cost one Lua registration and
+/// each only runs for the [`EventKind`]s in its `filter` instead of every
+/// closure running for every event
+#[derive(Debug, Clone)]
+pub struct EventBus<'lua>(World<'lua>);
+
 impl<'lua> World<'lua> {
     pub fn get(lua: &'lua Lua) -> LuaResult<Self> {
         lua.globals().raw_get("world")
     }
 
-    pub fn add_event_handler<F>(&self, f: F) -> LuaResult<HandlerId>
+    pub fn events(&self) -> EventBus<'lua> {
+        EventBus(self.clone())
+    }
+
+    pub fn get_player(&self) -> LuaResult<Sequence<Unit>> {
+        self.t.call_method("getPlayer", ())
+    }
+
+    pub fn get_airbases(&self) -> LuaResult<Sequence<Airbase>> {
+        self.t.call_method("getAirbases", ())
+    }
+
+    /// start teeing every [`Event`] passing through the [`EventBus`] into a
+    /// ring buffer of at most `capacity` entries (oldest dropped first),
+    /// for automated testing and post-mission analysis; see [`Recorder`]
+    pub fn start_recording(&self, capacity: usize) -> Recorder {
+        unsafe {
+            RECORDING = Some(Recording {
+                capacity: capacity.max(1),
+                buf: VecDeque::with_capacity(capacity.min(1024)),
+            });
+        }
+        Recorder(())
+    }
+
+    /// feed `events` through `handler` in the order given without
+    /// touching the live DCS `addEventHandler`, so a test can assert
+    /// handler behavior against a fixed, repeatable sequence of events it
+    /// builds itself, instead of a live mission
+    pub fn replay<F>(&self, events: impl IntoIterator<Item = (f64, Event<'lua>)>, mut handler: F) -> LuaResult<()>
     where
-        F: Fn(&'lua Lua, Event) -> LuaResult<()> + 'static,
+        F: FnMut(&Lua, f64, &Event) -> LuaResult<Control>,
     {
-        let globals = self.lua.globals();
-        let id = HandlerId::new();
-        let tbl = self.lua.create_table()?;
+        for (t, ev) in events {
+            match handler(self.lua, t, &ev)? {
+                Control::Continue => (),
+                Control::Stop => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// poll the futures pending from [`EventBus::subscribe_async`]
+    /// handlers with a noop waker, dropping the ones that finish. A newly
+    /// queued future is polled on the next call; after that a future is
+    /// polled again only once it has woken its waker, and one that returns
+    /// `Pending` without waking is not polled again. Call this once per sim frame (e.g. from a `Timer::schedule_function`
+    /// callback) so a handler can `await` across frames without ever
+    /// blocking `onEvent`
+    pub fn poll_handlers(&self) {
+        let waker = noop_waker();
+        let mut cx = TaskCx::from_waker(&waker);
+        let pending = pending();
+        while let Poll::Ready(Some(res)) = pending.poll_next_unpin(&mut cx) {
+            if let Err(e) = res {
+                println!("async event handler failed, {:?}", e);
+            }
+        }
+    }
+}
+
+impl<'lua> EventBus<'lua> {
+    /// register the shared `addEventHandler` table with DCS if neither
+    /// [`subscribe`](Self::subscribe) nor [`subscribe_async`](Self::subscribe_async)
+    /// has done so yet
+    fn ensure_registered(&self, already_registered: bool) -> LuaResult<()> {
+        let world = &self.0;
+        let globals = world.lua.globals();
+        // the table stays registered after the last subscriber is removed,
+        // so a later first subscriber must not add a second one
+        if already_registered || !matches!(globals.raw_get::<_, Value>("rustEventBus")?, Value::Nil) {
+            return Ok(());
+        }
+        let tbl = world.lua.create_table()?;
         tbl.set(
             "onEvent",
-            self.lua
-                .create_function(move |lua, (_, ev): (Value, Event)| f(lua, ev))?,
+            world
+                .lua
+                .create_function(move |lua, (_, ev): (Value, Event)| dispatch(lua, ev))?,
         )?;
-        self.t.call_method("addEventHandler", tbl.clone())?;
-        globals.raw_set(id.key(), tbl)?;
+        world.t.call_method("addEventHandler", tbl.clone())?;
+        // stash it in a global so DCS's Lua state doesn't garbage
+        // collect the handler table out from under `addEventHandler`
+        globals.raw_set("rustEventBus", tbl)?;
+        Ok(())
+    }
+
+    /// subscribe to events whose [`EventKind`] is in `filter`; subscribers
+    /// run in registration order and `f` returning `Control::Stop` keeps
+    /// later `subscribe` subscribers from seeing that one event;
+    /// [`subscribe_async`](Self::subscribe_async) subscribers are still
+    /// handed it
+    pub fn subscribe<F>(&self, filter: BitFlags<EventKind>, f: F) -> LuaResult<HandlerId>
+    where
+        F: FnMut(&Lua, &Event) -> LuaResult<Control> + 'static,
+    {
+        let id = HandlerId::new();
+        let already_registered = unsafe {
+            let already = !SUBSCRIBERS.is_empty() || !ASYNC_SUBSCRIBERS.is_empty();
+            SUBSCRIBERS.push(Subscriber {
+                id,
+                filter,
+                f: Box::new(f),
+            });
+            already
+        };
+        self.ensure_registered(already_registered)?;
         Ok(id)
     }
 
-    pub fn remove_event_handler(&self, id: HandlerId) -> LuaResult<()> {
-        let globals = self.lua.globals();
-        let key = id.key();
-        let handler = globals.raw_get(key.clone())?;
-        let handler = as_tbl("EventHandler", None, handler)?;
-        self.t.call_method("removeEventHandler", handler)?;
-        globals.raw_remove(key)?;
-        Ok(())
+    /// like [`subscribe`](Self::subscribe), but `f` returns a future instead
+    /// of handling the event inline. The future is fired with an owned
+    /// [`Event`] and must be `'static`, so `f` has to pull whatever it needs
+    /// out of the event before its first `await`: nothing here can hold a
+    /// borrow of the `Lua` that fired it across a frame boundary. Dropped
+    /// futures (e.g. from [`unsubscribe`](Self::unsubscribe)) are simply
+    /// cancelled, same as any other future.
+    pub fn subscribe_async<F, Fut>(&self, filter: BitFlags<EventKind>, f: F) -> LuaResult<HandlerId>
+    where
+        F: Fn(&Lua, Event) -> Fut + 'static,
+        Fut: Future<Output = LuaResult<()>> + 'static,
+    {
+        let id = HandlerId::new();
+        let already_registered = unsafe {
+            let already = !SUBSCRIBERS.is_empty() || !ASYNC_SUBSCRIBERS.is_empty();
+            ASYNC_SUBSCRIBERS.push(AsyncSubscriber {
+                id,
+                filter,
+                f: Box::new(move |lua, ev| Box::pin(f(lua, ev))),
+            });
+            already
+        };
+        self.ensure_registered(already_registered)?;
+        Ok(id)
     }
 
-    pub fn get_player(&self) -> LuaResult<Sequence<Unit>> {
-        self.t.call_method("getPlayer", ())
+    pub fn unsubscribe(&self, id: HandlerId) {
+        remove(id)
     }
 
-    pub fn get_airbases(&self) -> LuaResult<Sequence<Airbase>> {
-        self.t.call_method("getAirbases", ())
+    /// like [`subscribe`](Self::subscribe), but returns a [`HandlerGuard`]
+    /// instead of a bare `HandlerId`, so a subscription that's scoped to,
+    /// say, a single menu item or a single spawned group can't outlive its
+    /// owner by accident
+    pub fn subscribe_scoped<F>(&self, filter: BitFlags<EventKind>, f: F) -> LuaResult<HandlerGuard>
+    where
+        F: FnMut(&Lua, &Event) -> LuaResult<Control> + 'static,
+    {
+        Ok(HandlerGuard(Some(self.subscribe(filter, f)?)))
+    }
+
+    /// the [`subscribe_async`](Self::subscribe_async) counterpart of
+    /// [`subscribe_scoped`](Self::subscribe_scoped)
+    pub fn subscribe_async_scoped<F, Fut>(
+        &self,
+        filter: BitFlags<EventKind>,
+        f: F,
+    ) -> LuaResult<HandlerGuard>
+    where
+        F: Fn(&Lua, Event) -> Fut + 'static,
+        Fut: Future<Output = LuaResult<()>> + 'static,
+    {
+        Ok(HandlerGuard(Some(self.subscribe_async(filter, f)?)))
+    }
+}
+
+/// an RAII handle to a [`EventBus::subscribe_scoped`]/
+/// [`EventBus::subscribe_async_scoped`] registration: dropping it
+/// unsubscribes the handler, so a forgotten cleanup call can't leak a
+/// subscription (and, for an async handler, leave its future parked in
+/// [`World::poll_handlers`]) for the rest of the mission.
+///
+/// Unlike a per-handler Lua registration, tearing this down never needs to
+/// re-enter Lua: [`EventBus`] already keeps exactly one `addEventHandler`
+/// table alive for as long as *any* subscriber, sync or async, still
+/// exists, so dropping a guard only has to pull its `HandlerId` out of the
+/// in-process subscriber lists.
+#[derive(Debug)]
+pub struct HandlerGuard(Option<HandlerId>);
+
+impl HandlerGuard {
+    /// stop auto-unsubscribing on drop and hand back the `HandlerId` for
+    /// manual lifetime management via [`EventBus::unsubscribe`]
+    pub fn forget(mut self) -> HandlerId {
+        self.0.take().expect("HandlerGuard id already taken")
+    }
+}
+
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.0.take() {
+            remove(id);
+        }
     }
 }
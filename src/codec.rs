@@ -0,0 +1,127 @@
+//! round-trip conversion between Lua values and `serde_json::Value`, so
+//! external config (spawn templates, player records, ...) can be authored
+//! as JSON and loaded straight into the scripting environment, and events
+//! can be shipped back out the same way.
+use mlua::{prelude::*, Value};
+use serde_json::{json, Map, Number, Value as JVal};
+
+/// what to do with a Lua value that has no JSON representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsupportedPolicy {
+    /// fail the whole conversion
+    Error,
+    /// drop the key/index entirely
+    Skip,
+    /// stringify it, e.g. `"<Function>"`, same as the old one-way converter
+    Placeholder,
+}
+
+fn unsupported(policy: UnsupportedPolicy, what: &'static str) -> LuaResult<Option<JVal>> {
+    match policy {
+        UnsupportedPolicy::Error => Err(mlua::Error::RuntimeError(format!(
+            "can't represent a Lua {what} as JSON"
+        ))),
+        UnsupportedPolicy::Skip => Ok(None),
+        UnsupportedPolicy::Placeholder => Ok(Some(json!(format!("<{what}>")))),
+    }
+}
+
+/// a Lua table is a JSON array iff its keys are exactly the contiguous
+/// integers `1..=len`, which is the convention DCS and mission scripts use
+/// for sequences
+fn as_array(tbl: &mlua::Table) -> LuaResult<Option<Vec<(i64, Value)>>> {
+    let len = tbl.raw_len();
+    if len == 0 {
+        return Ok(None);
+    }
+    let mut entries = Vec::with_capacity(len as usize);
+    for pair in tbl.clone().pairs::<Value, Value>() {
+        let (k, v) = pair?;
+        match k {
+            Value::Integer(i) if i >= 1 && i <= len => entries.push((i, v)),
+            _ => return Ok(None),
+        }
+    }
+    if entries.len() as i64 != len {
+        return Ok(None);
+    }
+    entries.sort_by_key(|(i, _)| *i);
+    Ok(Some(entries))
+}
+
+pub fn to_json(v: &Value, policy: UnsupportedPolicy) -> LuaResult<Option<JVal>> {
+    Ok(match v {
+        Value::Nil => Some(JVal::Null),
+        Value::Boolean(b) => Some(json!(b)),
+        Value::Integer(i) => Some(json!(*i)),
+        // force a float representation even for whole numbers, so a
+        // round trip through `from_json` doesn't turn 2.0 into the
+        // integer 2
+        Value::Number(n) => Some(JVal::Number(
+            Number::from_f64(*n).unwrap_or_else(|| Number::from(0)),
+        )),
+        Value::String(s) => Some(json!(s.to_str()?)),
+        Value::Table(tbl) => match as_array(tbl)? {
+            Some(entries) => {
+                let mut arr = Vec::with_capacity(entries.len());
+                for (_, v) in entries {
+                    if let Some(v) = to_json(&v, policy)? {
+                        arr.push(v)
+                    }
+                }
+                Some(JVal::Array(arr))
+            }
+            None => {
+                let mut map = Map::new();
+                for pair in tbl.clone().pairs::<Value, Value>() {
+                    let (k, v) = pair?;
+                    let key = match &k {
+                        Value::String(s) => s.to_str()?.to_owned(),
+                        k => match to_json(k, policy)? {
+                            Some(k) => k.to_string(),
+                            None => continue,
+                        },
+                    };
+                    if let Some(v) = to_json(&v, policy)? {
+                        map.insert(key, v);
+                    }
+                }
+                Some(JVal::Object(map))
+            }
+        },
+        Value::LightUserData(_) => unsupported(policy, "LightUserData")?,
+        Value::UserData(_) => unsupported(policy, "UserData")?,
+        Value::Function(_) => unsupported(policy, "Function")?,
+        Value::Thread(_) => unsupported(policy, "Thread")?,
+        Value::Error(e) => Some(json!(format!("{e}"))),
+    })
+}
+
+pub fn from_json<'lua>(lua: &'lua Lua, v: &JVal) -> LuaResult<Value<'lua>> {
+    Ok(match v {
+        JVal::Null => Value::Nil,
+        JVal::Bool(b) => Value::Boolean(*b),
+        JVal::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::Integer(i)
+            } else {
+                Value::Number(n.as_f64().unwrap_or(0.))
+            }
+        }
+        JVal::String(s) => Value::String(lua.create_string(s)?),
+        JVal::Array(arr) => {
+            let tbl = lua.create_table()?;
+            for (i, v) in arr.iter().enumerate() {
+                tbl.raw_set(i as i64 + 1, from_json(lua, v)?)?;
+            }
+            Value::Table(tbl)
+        }
+        JVal::Object(map) => {
+            let tbl = lua.create_table()?;
+            for (k, v) in map {
+                tbl.raw_set(k.as_str(), from_json(lua, v)?)?;
+            }
+            Value::Table(tbl)
+        }
+    })
+}
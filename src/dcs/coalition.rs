@@ -0,0 +1,46 @@
+use super::{
+    airbase::Airbase,
+    as_tbl,
+    group::{Group, GroupCategory},
+    static_object::StaticObject,
+    unit::Unit,
+};
+use crate::{simple_enum, wrapped_table};
+use mlua::{prelude::*, Value};
+use serde_derive::Serialize;
+
+simple_enum!(Side, u8, [Neutral => 0, Red => 1, Blue => 2]);
+
+wrapped_table!(Coalition, None);
+
+impl<'lua> Coalition<'lua> {
+    pub fn singleton(lua: &'lua Lua) -> LuaResult<Self> {
+        lua.globals().raw_get("coalition")
+    }
+
+    pub fn get_groups(
+        &self,
+        side: Side,
+        category: GroupCategory,
+    ) -> LuaResult<impl Iterator<Item = LuaResult<Group<'lua>>>> {
+        Ok(
+            as_tbl("Groups", None, self.t.call_method("getGroups", (side, category))?)?
+                .sequence_values(),
+        )
+    }
+
+    pub fn get_static_objects(&self, side: Side) -> LuaResult<impl Iterator<Item = LuaResult<StaticObject<'lua>>>> {
+        Ok(
+            as_tbl("StaticObjects", None, self.t.call_method("getStaticObjects", side)?)?
+                .sequence_values(),
+        )
+    }
+
+    pub fn get_airbases(&self, side: Side) -> LuaResult<impl Iterator<Item = LuaResult<Airbase<'lua>>>> {
+        Ok(as_tbl("Airbases", None, self.t.call_method("getAirbases", side)?)?.sequence_values())
+    }
+
+    pub fn get_players(&self, side: Side) -> LuaResult<impl Iterator<Item = LuaResult<Unit<'lua>>>> {
+        Ok(as_tbl("Players", None, self.t.call_method("getPlayers", side)?)?.sequence_values())
+    }
+}
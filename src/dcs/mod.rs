@@ -13,6 +13,7 @@ pub mod airbase;
 pub mod warehouse;
 pub mod coalition;
 pub mod country;
+pub mod shared;
 pub mod static_object;
 
 #[macro_export]
@@ -212,6 +213,16 @@ impl<'lua> FromLua<'lua> for Vec3 {
     }
 }
 
+impl<'lua> IntoLua<'lua> for Vec3 {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let tbl = lua.create_table()?;
+        tbl.raw_set("x", self.x)?;
+        tbl.raw_set("y", self.y)?;
+        tbl.raw_set("z", self.z)?;
+        Ok(Value::Table(tbl))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Position3 {
     p: Vec3,
@@ -232,6 +243,17 @@ impl<'lua> FromLua<'lua> for Position3 {
     }
 }
 
+impl<'lua> IntoLua<'lua> for Position3 {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let tbl = lua.create_table()?;
+        tbl.raw_set("p", self.p)?;
+        tbl.raw_set("x", self.x)?;
+        tbl.raw_set("y", self.y)?;
+        tbl.raw_set("z", self.z)?;
+        Ok(Value::Table(tbl))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Box3 {
     pub min: Vec3,
@@ -248,6 +270,15 @@ impl<'lua> FromLua<'lua> for Box3 {
     }
 }
 
+impl<'lua> IntoLua<'lua> for Box3 {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let tbl = lua.create_table()?;
+        tbl.raw_set("min", self.min)?;
+        tbl.raw_set("max", self.max)?;
+        Ok(Value::Table(tbl))
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 #[repr(transparent)]
 pub struct String(compact_str::CompactString);
@@ -294,10 +325,14 @@ impl<'lua> FromLua<'lua> for Time {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// discriminants match DCS's `world.VolumeType.*` constants, which is what
+/// [`world::SearchVolume`]'s `IntoLua` impl sends as the search volume
+/// table's `id` field
+#[derive(Debug, Clone, Copy, Serialize)]
+#[repr(u8)]
 pub enum VolumeType {
-    Segment,
-    Box,
-    Sphere,
-    Pyramid,
+    Segment = 0,
+    Box = 1,
+    Sphere = 2,
+    Pyramid = 3,
 }
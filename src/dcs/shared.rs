@@ -0,0 +1,39 @@
+//! publishes a long-lived Rust value to mission scripts as a named global,
+//! instead of hand-rolling one like [`world::HandlerId::key`](super::world::HandlerId)
+//! does for its per-handler registration table. `T` implements [`UserData`]
+//! with whatever methods the Lua side should see; wrapping it in an `Arc`
+//! and handing that to Lua (rather than the bare value) is what lets a
+//! `World` event handler and the Lua state both hold a reference to the
+//! same state at once, so the handler can keep its own `Arc` clone around
+//! across ticks and mutate through interior mutability instead of
+//! round-tripping data back into Lua globals by hand.
+use mlua::{prelude::*, UserData};
+use std::sync::Arc;
+
+/// publish `state` as the Lua global `name`. `Arc<T>`'s blanket
+/// `UserData`/`IntoLua` impls do the actual conversion; this just picks a
+/// place to put it that mission scripts can find by name.
+pub fn publish<'lua, T>(lua: &'lua Lua, name: &str, state: Arc<T>) -> LuaResult<()>
+where
+    T: UserData + Send + Sync + 'static,
+{
+    lua.globals().raw_set(name, state)
+}
+
+/// fetch a previously [`publish`]ed `Arc<T>` back out of the Lua global
+/// `name`, e.g. from inside a `World::add_event_handler`/
+/// `add_async_event_handler` closure that needs to touch the same shared
+/// state the Lua side was handed
+pub fn fetch<'lua, T>(lua: &'lua Lua, name: &str) -> LuaResult<Arc<T>>
+where
+    T: UserData + Send + Sync + 'static,
+{
+    lua.globals().raw_get(name)
+}
+
+/// removes the global `name` added by [`publish`], e.g. when the state it
+/// held is torn down at the end of a mission and Lua shouldn't be able to
+/// reach it anymore
+pub fn remove(lua: &Lua, name: &str) -> LuaResult<()> {
+    lua.globals().raw_remove(name)
+}
@@ -1,10 +1,26 @@
-use super::{as_tbl, event::Event, unit::Unit, String};
+use super::{
+    airbase::Airbase,
+    as_tbl,
+    event::Event,
+    object::{Object, ObjectCategory},
+    unit::Unit,
+    Box3, Position3, Vec3, VolumeType, String,
+};
 use compact_str::format_compact;
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, StreamExt};
 use mlua::{prelude::*, Value};
 use serde_derive::Serialize;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context as TaskCx, Poll, RawWaker, RawWakerVTable, Waker},
+};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct HandlerId(u32);
 
 impl HandlerId {
@@ -18,6 +34,96 @@ impl HandlerId {
     }
 }
 
+/// one [`World::add_async_event_handler`] registration: `f` is called
+/// synchronously to produce a future per matching event, but only one of
+/// those futures is ever polled at a time for a given handler. events
+/// that arrive while the previous one is still pending are buffered in
+/// `queue` rather than raced against it or dropped.
+struct AsyncHandler {
+    id: HandlerId,
+    f: Box<dyn Fn(&'static Lua, Event) -> LocalBoxFuture<'static, LuaResult<()>>>,
+    queue: VecDeque<Event>,
+    busy: bool,
+}
+
+/// a future produced by an [`AsyncHandler`], tagged with the id it came
+/// from so a completed poll knows which handler's `queue` to pull from
+/// next, and so [`World::remove_async_event_handler`] can drop in-flight
+/// work along with future dispatches
+struct Pending {
+    id: HandlerId,
+    fut: LocalBoxFuture<'static, LuaResult<()>>,
+}
+
+impl Future for Pending {
+    type Output = (HandlerId, LuaResult<()>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskCx) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.fut.as_mut().poll(cx).map(|res| (this.id, res))
+    }
+}
+
+// DCS's scripting api is single threaded and `dispatch_async` only ever
+// runs from inside the one `onEvent` callback DCS calls back into on that
+// same thread, so bare statics are sound here as long as nothing re-enters
+// them concurrently. treating the embedding `Lua` as `'static` is the same
+// assumption `add_event_handler`'s `F: Fn(&'lua Lua, ..)` already makes:
+// the module's Lua state outlives the mission, not any single callback.
+static mut ASYNC_HANDLERS: Vec<AsyncHandler> = Vec::new();
+static mut PENDING: Option<FuturesUnordered<Pending>> = None;
+
+fn pending() -> &'static mut FuturesUnordered<Pending> {
+    unsafe { PENDING.get_or_insert_with(FuturesUnordered::new) }
+}
+
+// a waker that does nothing: `FuturesUnordered` still polls a pending future
+// again only after that future wakes the per-task waker it wraps around
+// this one; a future that returns `Pending` without waking is not polled again
+const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |_| RawWaker::new(std::ptr::null(), &NOOP_VTABLE),
+    |_| (),
+    |_| (),
+    |_| (),
+);
+
+fn noop_waker() -> Waker {
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &NOOP_VTABLE)) }
+}
+
+fn start(lua: &'static Lua, handler: &mut AsyncHandler, ev: Event) {
+    let fut = (handler.f)(lua, ev);
+    handler.busy = true;
+    pending().push(Pending { id: handler.id, fut });
+}
+
+fn dispatch_async(lua: &'static Lua, id: HandlerId, ev: Event) -> LuaResult<()> {
+    unsafe {
+        if let Some(handler) = ASYNC_HANDLERS.iter_mut().find(|h| h.id == id) {
+            if handler.busy {
+                handler.queue.push_back(ev);
+            } else {
+                start(lua, handler, ev);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// called with the id of a future that just finished: start the next
+/// queued event for that handler, if any, or else mark it idle so the
+/// next [`dispatch_async`] starts one immediately instead of queueing it
+fn advance_async(lua: &'static Lua, id: HandlerId) {
+    unsafe {
+        if let Some(handler) = ASYNC_HANDLERS.iter_mut().find(|h| h.id == id) {
+            match handler.queue.pop_front() {
+                Some(ev) => start(lua, handler, ev),
+                None => handler.busy = false,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct World<'lua> {
     t: mlua::Table<'lua>,
@@ -70,5 +176,179 @@ impl<'lua> World<'lua> {
         Ok(as_tbl("Players", None, self.t.call_method("getPlayer", ())?)?.sequence_values())
     }
 
-    // pub fn get_airbases(&self) -> LuaReslt<>
+    pub fn get_airbases(&self) -> LuaResult<impl Iterator<Item = LuaResult<Airbase>>> {
+        Ok(as_tbl("Airbases", None, self.t.call_method("getAirbases", ())?)?.sequence_values())
+    }
+
+    /// `world.searchObjects`: invoke `f` for every object of `category`
+    /// inside `volume`, in whatever order DCS visits them. `f` returning
+    /// `Ok(false)` stops the search early, the same contract DCS's own
+    /// handler callback has.
+    pub fn search_objects<F>(&self, category: ObjectCategory, volume: SearchVolume, mut f: F) -> LuaResult<()>
+    where
+        F: FnMut(&'lua Lua, Object) -> LuaResult<bool> + 'static,
+    {
+        let handler = self
+            .lua
+            .create_function(move |lua, (obj, _data): (Object, Value)| f(lua, obj))?;
+        self.t.call_method("searchObjects", (category, volume, handler))
+    }
+
+    /// like [`search_objects`](Self::search_objects), but collects every
+    /// matching object into a `Vec` instead of taking a per-object
+    /// callback, for the common case where the caller just wants
+    /// "everything in this volume" rather than early-exit control
+    pub fn search_objects_collect(
+        &self,
+        category: ObjectCategory,
+        volume: SearchVolume,
+    ) -> LuaResult<Vec<Object<'lua>>> {
+        let found = Rc::new(RefCell::new(Vec::new()));
+        let found_ref = found.clone();
+        self.search_objects(category, volume, move |_, obj| {
+            found_ref.borrow_mut().push(obj);
+            Ok(true)
+        })?;
+        // mlua only frees the boxed closure handed to `searchObjects` on a
+        // later Lua GC cycle, not when the call returns, so `found` can
+        // still have a second `Rc` clone alive here; take the contents
+        // instead of trying to unwrap the `Rc` itself
+        Ok(std::mem::take(&mut *found.borrow_mut()))
+    }
+}
+
+/// the geometry DCS's `world.searchObjects` accepts, one variant per
+/// [`VolumeType`]. Field names follow the Lua table shape the engine
+/// expects in `params`, not Rust naming: e.g. [`Self::Pyramid`]'s
+/// `half_angle_hor`/`half_angle_ver` become `halfAngleHor`/`halfAngleVer`.
+#[derive(Debug, Clone)]
+pub enum SearchVolume {
+    Segment { from: Vec3, to: Vec3 },
+    Box(Box3),
+    Sphere { point: Vec3, radius: f64 },
+    Pyramid {
+        pos: Position3,
+        length: f64,
+        half_angle_hor: f64,
+        half_angle_ver: f64,
+    },
+}
+
+impl<'lua> IntoLua<'lua> for SearchVolume {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let params = lua.create_table()?;
+        let id = match self {
+            SearchVolume::Segment { from, to } => {
+                params.raw_set("from", from)?;
+                params.raw_set("to", to)?;
+                VolumeType::Segment
+            }
+            SearchVolume::Box(b) => {
+                params.raw_set("min", b.min)?;
+                params.raw_set("max", b.max)?;
+                VolumeType::Box
+            }
+            SearchVolume::Sphere { point, radius } => {
+                params.raw_set("point", point)?;
+                params.raw_set("radius", radius)?;
+                VolumeType::Sphere
+            }
+            SearchVolume::Pyramid {
+                pos,
+                length,
+                half_angle_hor,
+                half_angle_ver,
+            } => {
+                params.raw_set("pos", pos)?;
+                params.raw_set("length", length)?;
+                params.raw_set("halfAngleHor", half_angle_hor)?;
+                params.raw_set("halfAngleVer", half_angle_ver)?;
+                VolumeType::Pyramid
+            }
+        };
+        let tbl = lua.create_table()?;
+        tbl.raw_set("id", id as i64)?;
+        tbl.raw_set("params", params)?;
+        Ok(Value::Table(tbl))
+    }
+}
+
+/// the [`add_event_handler`](World::add_event_handler)/
+/// [`remove_event_handler`](World::remove_event_handler) pair requires `f`
+/// to finish synchronously inside `onEvent`, which blocks the sim thread
+/// for as long as `f` takes. These two do the same registration dance but
+/// let `f` return a future instead, driven to completion across later
+/// [`poll_async_handlers`](World::poll_async_handlers) calls rather than
+/// inline.
+///
+/// re-entrancy: `f` is only ever invoked again for a given handler after
+/// its previous future resolves (see [`AsyncHandler::queue`]), so a
+/// handler never has to guard against a second copy of itself running
+/// concurrently against the same `Lua` state. Handlers for *different*
+/// ids, however, can have futures pending at the same time, and DCS can
+/// still call back into `onEvent` for a sync handler while an async one is
+/// parked — `f` must not assume it has the scripting thread to itself for
+/// as long as its future lives.
+impl<'lua> World<'lua>
+where
+    'lua: 'static,
+{
+    pub fn add_async_event_handler<F, Fut>(&self, f: F) -> LuaResult<HandlerId>
+    where
+        F: Fn(&'lua Lua, Event) -> Fut + 'static,
+        Fut: Future<Output = LuaResult<()>> + 'static,
+    {
+        let globals = self.lua.globals();
+        let id = HandlerId::new();
+        let tbl = self.lua.create_table()?;
+        tbl.set(
+            "onEvent",
+            self.lua
+                .create_function(move |lua, (_, ev): (Value, Event)| dispatch_async(lua, id, ev))?,
+        )?;
+        self.t.call_method("addEventHandler", tbl.clone())?;
+        globals.raw_set(id.key(), tbl)?;
+        unsafe {
+            ASYNC_HANDLERS.push(AsyncHandler {
+                id,
+                f: Box::new(move |lua, ev| Box::pin(f(lua, ev))),
+                queue: VecDeque::new(),
+                busy: false,
+            });
+        }
+        Ok(id)
+    }
+
+    pub fn remove_async_event_handler(&self, id: HandlerId) -> LuaResult<()> {
+        let globals = self.lua.globals();
+        let key = id.key();
+        let handler = globals.raw_get(key.clone())?;
+        let handler = as_tbl("EventHandler", None, handler)?;
+        self.t.call_method("removeEventHandler", handler)?;
+        globals.raw_remove(key)?;
+        unsafe {
+            ASYNC_HANDLERS.retain(|h| h.id != id);
+        }
+        pending().retain(|p| p.id != id);
+        Ok(())
+    }
+
+    /// poll the futures pending from [`add_async_event_handler`]
+    /// handlers with a noop waker, dropping the ones that finish and
+    /// starting the next queued event (if any) for the handler they came
+    /// from. A newly queued future is polled on the next call; after that a
+    /// future is polled again only once it has woken its waker, and one that
+    /// returns `Pending` without waking is not polled again. Call this once per sim frame, e.g. from a DCS `doUpdate`/timer
+    /// callback, so an async handler can work across frames without ever
+    /// blocking `onEvent`.
+    pub fn poll_async_handlers(&self) {
+        let waker = noop_waker();
+        let mut cx = TaskCx::from_waker(&waker);
+        while let Poll::Ready(Some((id, res))) = pending().poll_next_unpin(&mut cx) {
+            if let Err(e) = res {
+                println!("async event handler failed, {:?}", e);
+            }
+            advance_async(self.lua, id);
+        }
+    }
 }
\ No newline at end of file